@@ -12,6 +12,14 @@ fn main() -> std::io::Result<()> {
     let cmd = nilla_cli_def::Cli::command().after_long_help(format!(
         "
 {HEADER_STYLE}Valid project sources{HEADER_STYLE:#}
+  registry
+
+    Use a project source by name from the user registry in
+    $XDG_CONFIG_HOME/nilla/registry.json or the system registry in
+    /etc/nilla/registry.json. Entries are managed with `nilla registry`.
+
+      <name>
+
   path
 
     Fetch a Nilla project from a file path. This follows the format:
@@ -38,6 +46,11 @@ fn main() -> std::io::Result<()> {
 
       github:<owner>/<repo>?rev=<rev>&dir=<dir>
 
+    Repositories pinned to a rev can be fetched as an archive instead of being
+    cloned. This is also supported by gitlab, codeberg, forgejo, gitea and sourcehut:
+
+      github:<owner>/<repo>?rev=<rev>&fetch=tarball
+
   gitlab
 
     Fetch a Nilla project from a GitLab repository. This follows the format:
@@ -48,6 +61,37 @@ fn main() -> std::io::Result<()> {
 
       gitlab:<owner>/<repo>?rev=<rev>&dir=<dir>
 
+  codeberg
+
+    Fetch a Nilla project from a Codeberg repository. This follows the format:
+
+      codeberg:<owner>/<repo>
+
+    Optionally, additional customization can be applied using query parameters:
+
+      codeberg:<owner>/<repo>?rev=<rev>&dir=<dir>
+
+  forgejo, gitea, forge
+
+    Fetch a Nilla project from a repository on a self-hosted forge. The host must
+    be given as a query parameter. This follows the format:
+
+      forgejo:<owner>/<repo>?host=<host>
+
+    Optionally, additional customization can be applied using query parameters:
+
+      forgejo:<owner>/<repo>?host=<host>&rev=<rev>&dir=<dir>&method=ssh
+
+  sourcehut
+
+    Fetch a Nilla project from a SourceHut repository. This follows the format:
+
+      sourcehut:~<owner>/<repo>
+
+    Optionally, additional customization can be applied using query parameters:
+
+      sourcehut:~<owner>/<repo>?rev=<rev>&ref=<ref>&dir=<dir>&host=<host>&method=ssh
+
   tarball
 
     Fetch a Nilla project from a tarball. This follows the format:
@@ -60,6 +104,31 @@ fn main() -> std::io::Result<()> {
       tarball:http://example.com/project.tar.gz
 
       http://example.com/project.tar.gz
+
+    Optionally, the project directory and expected hash can be given using query
+    parameters. Any other query parameters are passed on to the server:
+
+      tarball:<url>?dir=<dir>&narHash=<hash>
+
+    Archives of GitHub and GitLab repositories can be fetched without cloning:
+
+      tarball:github:<owner>/<repo>?rev=<rev>&dir=<dir>
+
+      tarball:gitlab:<owner>/<repo>?ref=<ref>&host=<host>
+
+{HEADER_STYLE}Configuration{HEADER_STYLE:#}
+  Defaults for options are read from /etc/nilla/config.toml, then
+  $XDG_CONFIG_HOME/nilla/config.toml, then the closest .nilla.toml found by
+  searching up from the current directory, and finally NILLA_* environment
+  variables. The local config is found from the current directory, not from
+  --project. Invalid files, keys and values are skipped with a warning.
+
+{HEADER_STYLE}Exit status{HEADER_STYLE:#}
+  0  Success
+  1  Any other failure
+  3  The project could not be resolved
+  4  A Nix evaluation failed
+  5  A Nix build failed
 "
     ));
 
@@ -1,18 +1,40 @@
 use clap::{ArgAction, Args};
 #[derive(Debug, Args)]
 #[command(
-	about = "Build a package from a Nilla project",
+	about = "Build packages from a Nilla project",
 	after_help = super::make_examples(&[
 		("Build a package from a local Nilla project.", "build mypackage"),
 		("Build a package from a Nilla project on GitHub.", "build mypackage --project github:myuser/myrepo"),
 		("Build a package from a Nilla project in a tarball.", "build mypackage --project https://example.com/myproject.tar.gz"),
+		("Build several packages and a shell at once.", "build mypackage otherpackage shells.dev"),
+		("Build every package for multiple systems.", "build --all-packages --system x86_64-linux --system aarch64-linux"),
 	])
 )]
 pub struct BuildArgs {
-    #[arg(help = "Name of the shell to start, if left empty it will use the default")]
-    pub name: Option<String>,
-    #[arg(help = "System architecture (eg: x86_64-linux)")]
-    pub system: Option<String>,
+    #[arg(
+        help = "Names of the packages or attribute paths to build, if left empty it will use the default. A system of the project given after a single name is accepted for compatibility, but deprecated in favor of --system"
+    )]
+    pub names: Vec<String>,
+    #[arg(
+        long,
+		action = ArgAction::Append,
+        help = "System architecture to build for (eg: x86_64-linux), can be given multiple times"
+    )]
+    pub system: Vec<String>,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Build every package available for the selected systems",
+        default_value_t = false
+    )]
+    pub all_packages: bool,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Build every shell available for the selected systems",
+        default_value_t = false
+    )]
+    pub all_shells: bool,
     #[arg(
         long,
 		action = ArgAction::SetTrue,
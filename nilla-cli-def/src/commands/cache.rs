@@ -0,0 +1,21 @@
+use clap::{Args, Subcommand};
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Manage cached evaluation results and project source resolutions",
+	after_help = super::make_examples(&[
+		("Remove all cached evaluation results.", "cache clear"),
+		("Build a package without using cached evaluation results.", "build mypackage --no-eval-cache"),
+		("Refetch a remote project instead of using its cached resolution.", "build mypackage --project github:myuser/myrepo --refresh"),
+	])
+)]
+pub struct CacheArgs {
+    #[command(subcommand)]
+    pub command: CacheCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CacheCommands {
+    #[command(about = "Remove all cached results")]
+    Clear,
+}
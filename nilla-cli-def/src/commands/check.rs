@@ -0,0 +1,22 @@
+use clap::{ArgAction, Args};
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Check a Nilla project for failed assertions and warnings",
+	after_help = super::make_examples(&[
+		("Check the assertions and warnings of a local Nilla project.", "check"),
+		("Check a Nilla project and build all of its packages and shells.", "check --build"),
+		("Check a Nilla project on GitHub and build everything for a specific system.", "check aarch64-linux --build --project github:myuser/myrepo"),
+	])
+)]
+pub struct CheckArgs {
+    #[arg(help = "System architecture (eg: x86_64-linux)")]
+    pub system: Option<String>,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Build every package and shell available for the system",
+        default_value_t = false
+    )]
+    pub build: bool,
+}
@@ -0,0 +1,47 @@
+use clap::{ArgAction, Args, Subcommand};
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Inspect and change the default options of Nilla",
+	after_help = super::make_examples(&[
+		("List the effective value of every option.", "config list"),
+		("Get the effective value of an option.", "config get system"),
+		("Never link build outputs to the current directory.", "config set build.no-link true"),
+		("Use a template by default for projects in the current directory.", "config set template minimal --local"),
+	])
+)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    pub command: ConfigCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommands {
+    #[command(about = "List every option with its effective value and where it was set")]
+    List,
+    #[command(about = "Get the effective value of an option")]
+    Get(ConfigGetArgs),
+    #[command(about = "Set an option in the user or local config file")]
+    Set(ConfigSetArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigGetArgs {
+    #[arg(help = "Name of the option (eg: build.no-link)")]
+    pub key: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ConfigSetArgs {
+    #[arg(help = "Name of the option (eg: build.no-link)")]
+    pub key: String,
+    #[arg(help = "Value to set the option to")]
+    pub value: String,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Write to the closest .nilla.toml, searching up from the current directory, instead of the user config",
+        default_value_t = false
+    )]
+    pub local: bool,
+}
@@ -0,0 +1,32 @@
+use clap::{ArgAction, Args};
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Evaluate an attribute of a Nilla project",
+	after_help = super::make_examples(&[
+		("Print the version of a package in a local Nilla project.", "eval packages.mypackage.result.x86_64-linux.version"),
+		("Print the names of all packages as JSON.", "eval packages --json --apply builtins.attrNames"),
+		("Print a string without quotes from a Nilla project on GitHub.", "eval packages.mypackage.result.x86_64-linux.name --raw --project github:myuser/myrepo"),
+	])
+)]
+pub struct EvalArgs {
+    #[arg(help = "The attribute path to evaluate (eg: packages.mypackage)")]
+    pub attribute: String,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Print the result as JSON",
+        default_value_t = false,
+        conflicts_with = "raw"
+    )]
+    pub json: bool,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Print the result as a raw string without quotes",
+        default_value_t = false
+    )]
+    pub raw: bool,
+    #[arg(long, help = "A Nix function to apply to the result before printing")]
+    pub apply: Option<String>,
+}
@@ -0,0 +1,39 @@
+use clap::{ArgAction, Args};
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Create a new Nilla project in the current directory",
+	after_help = super::make_examples(&[
+		("Create a Nilla project with a starter package and shell.", "init"),
+		("Create a Nilla project without any starter modules.", "init --template minimal"),
+		("Create a Nilla project from a template exposed by a Nilla project on GitHub.", "init --template rust --from github:myuser/mytemplates"),
+	])
+)]
+pub struct InitArgs {
+    #[command(flatten)]
+    pub template: TemplateArgs,
+}
+
+#[derive(Debug, Args)]
+pub struct TemplateArgs {
+    #[arg(
+        long,
+        short,
+        help = "Name of the template to use (built in: default, minimal, package, shell)",
+        default_value = "default"
+    )]
+    pub template: String,
+    #[arg(
+        long,
+        help = "A Nilla project to load the template from (check Valid project sources in the man pages)",
+        value_hint = clap::ValueHint::AnyPath
+    )]
+    pub from: Option<String>,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Overwrite existing files",
+        default_value_t = false
+    )]
+    pub force: bool,
+}
@@ -0,0 +1,51 @@
+use clap::{Args, Subcommand};
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Manage the inputs of a Nilla project",
+	after_help = super::make_examples(&[
+		("List the inputs of a local Nilla project.", "inputs list"),
+		("Update all pinned inputs of a local Nilla project.", "inputs update"),
+		("Update a single pinned input.", "inputs update nixpkgs"),
+		("Pin a new input from GitHub.", "inputs add mylib github:myuser/mylib?ref=main"),
+		("Remove a pinned input.", "inputs remove mylib"),
+	])
+)]
+pub struct InputsArgs {
+    #[command(subcommand)]
+    pub command: InputsCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum InputsCommands {
+    #[command(about = "List the inputs of a Nilla project and their pinned revisions")]
+    List,
+    #[command(about = "Update pinned inputs and show the changed revisions")]
+    Update(InputsUpdateArgs),
+    #[command(about = "Pin a new input")]
+    Add(InputsAddArgs),
+    #[command(about = "Remove a pinned input")]
+    Remove(InputsRemoveArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct InputsUpdateArgs {
+    #[arg(help = "Names of the pins to update, if left empty all pins are updated")]
+    pub names: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct InputsAddArgs {
+    #[arg(help = "Name of the pin to add")]
+    pub name: String,
+    #[arg(
+        help = "Source of the pin (eg: github:owner/repo?ref=main, gitlab:owner/repo, git:<url>, channel:nixos-unstable, <tarball url>)"
+    )]
+    pub source: String,
+}
+
+#[derive(Debug, Args)]
+pub struct InputsRemoveArgs {
+    #[arg(help = "Name of the pin to remove")]
+    pub name: String,
+}
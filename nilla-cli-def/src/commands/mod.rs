@@ -1,7 +1,16 @@
 use clap::builder::styling::Style;
 
 pub mod build;
+pub mod cache;
+pub mod check;
 pub mod completions;
+pub mod config;
+pub mod eval;
+pub mod init;
+pub mod inputs;
+pub mod new;
+pub mod registry;
+pub mod repl;
 pub mod run;
 pub mod shell;
 pub mod show;
@@ -0,0 +1,21 @@
+use std::path::PathBuf;
+
+use clap::Args;
+
+use super::init::TemplateArgs;
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Create a new Nilla project in a new directory",
+	after_help = super::make_examples(&[
+		("Create a Nilla project in a new directory.", "new myproject"),
+		("Create a Nilla project with only a starter shell.", "new myproject --template shell"),
+		("Create a Nilla project from a template exposed by a local Nilla project.", "new myproject --template rust --from ~/mytemplates"),
+	])
+)]
+pub struct NewArgs {
+    #[arg(help = "Directory to create the project in", value_hint = clap::ValueHint::DirPath)]
+    pub path: PathBuf,
+    #[command(flatten)]
+    pub template: TemplateArgs,
+}
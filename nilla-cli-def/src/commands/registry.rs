@@ -0,0 +1,53 @@
+use clap::{Args, Subcommand};
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Manage named aliases for Nilla projects",
+	after_help = super::make_examples(&[
+		("List all registry entries.", "registry list"),
+		("Add an alias for a project on GitHub.", "registry add infra 'github:myorg/infra?dir=nilla&ref=main'"),
+		("Build a package from an aliased project.", "build mypackage --project infra"),
+		("Pin an alias to the revision it currently resolves to.", "registry pin infra"),
+		("Remove an alias.", "registry remove infra"),
+	])
+)]
+pub struct RegistryArgs {
+    #[command(subcommand)]
+    pub command: RegistryCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RegistryCommands {
+    #[command(about = "List the entries of the user and system registries")]
+    List,
+    #[command(about = "Add an entry to the user registry")]
+    Add(RegistryAddArgs),
+    #[command(about = "Remove an entry from the user registry")]
+    Remove(RegistryRemoveArgs),
+    #[command(about = "Pin an entry to a revision in the user registry")]
+    Pin(RegistryPinArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct RegistryAddArgs {
+    #[arg(help = "Name of the entry")]
+    pub name: String,
+    #[arg(
+        help = "Project source the name refers to (check Valid project sources in the man pages)"
+    )]
+    pub source: String,
+}
+
+#[derive(Debug, Args)]
+pub struct RegistryRemoveArgs {
+    #[arg(help = "Name of the entry to remove")]
+    pub name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct RegistryPinArgs {
+    #[arg(help = "Name of the entry to pin")]
+    pub name: String,
+    #[arg(help = "Revision to pin to, if left empty the current revision is used")]
+    pub rev: Option<String>,
+}
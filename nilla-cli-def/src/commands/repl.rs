@@ -0,0 +1,15 @@
+use clap::Args;
+
+#[derive(Debug, Args)]
+#[command(
+	about = "Start a Nix REPL with a Nilla project loaded",
+	after_help = super::make_examples(&[
+		("Start a REPL for a local Nilla project.", "repl"),
+		("Start a REPL for a Nilla project on GitHub.", "repl --project github:myuser/myrepo"),
+		("Start a REPL with a specific system in scope.", "repl aarch64-linux"),
+	])
+)]
+pub struct ReplArgs {
+    #[arg(help = "System architecture (eg: x86_64-linux)")]
+    pub system: Option<String>,
+}
@@ -1,8 +1,12 @@
 pub mod commands;
 
-use clap::{ArgAction, Parser, Subcommand};
+use std::path::PathBuf;
+
+use clap::{ArgAction, Parser, Subcommand, ValueEnum};
 use commands::{
-    build::BuildArgs, completions::CompletionsArgs, run::RunArgs, shell::ShellArgs, show::ShowArgs,
+    build::BuildArgs, cache::CacheArgs, check::CheckArgs, completions::CompletionsArgs,
+    config::ConfigArgs, eval::EvalArgs, init::InitArgs, inputs::InputsArgs, new::NewArgs,
+    registry::RegistryArgs, repl::ReplArgs, run::RunArgs, shell::ShellArgs, show::ShowArgs,
 };
 
 #[derive(Parser, Debug)]
@@ -52,6 +56,75 @@ pub struct Cli {
 		default_value_t = false,
     )]
     pub show_eval_commands: bool,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Always re-evaluate instead of using cached evaluation results",
+        global = true,
+		default_value_t = false,
+    )]
+    pub no_eval_cache: bool,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Refetch remote project sources instead of using cached resolutions",
+        global = true,
+		default_value_t = false,
+    )]
+    pub refresh: bool,
+    #[arg(
+        long,
+        help = "The lock file to pin remote projects with, defaults to nilla.lock in the current directory if it exists",
+        value_hint = clap::ValueHint::FilePath,
+        global = true
+    )]
+    pub lock_file: Option<PathBuf>,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Resolve remote projects again and record the results in the lock file",
+        global = true,
+		default_value_t = false,
+    )]
+    pub update_lock: bool,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Include untracked files that are not ignored when using local git projects",
+        global = true,
+		default_value_t = false,
+    )]
+    pub include_untracked: bool,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Include checked out submodules when using local git projects",
+        global = true,
+		default_value_t = false,
+    )]
+    pub include_submodules: bool,
+    #[arg(
+        long,
+		action = ArgAction::SetTrue,
+        help = "Fail when a local git project has uncommitted changes",
+        global = true,
+		default_value_t = false,
+    )]
+    pub require_clean: bool,
+    #[arg(
+        long,
+        value_enum,
+        help = "The format to print command results in, run and shell print them to stderr",
+        global = true,
+        default_value_t = OutputFormat::Human,
+    )]
+    pub output: OutputFormat,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Human,
+    Json,
 }
 
 #[derive(Subcommand, Debug)]
@@ -61,6 +134,15 @@ pub enum Commands {
     Shell(ShellArgs),
     Run(RunArgs),
     Build(BuildArgs),
+    Check(CheckArgs),
+    Eval(EvalArgs),
+    Repl(ReplArgs),
+    Init(InitArgs),
+    New(NewArgs),
+    Inputs(InputsArgs),
+    Cache(CacheArgs),
+    Registry(RegistryArgs),
+    Config(ConfigArgs),
     #[command(alias = "completion")]
     Completions(CompletionsArgs),
     #[command(external_subcommand)]
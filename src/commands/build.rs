@@ -1,52 +1,104 @@
+use std::collections::{BTreeMap, BTreeSet};
+
 use anyhow::bail;
-use log::{debug, info};
-use serde_json::Value;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::util::{
+    errors::BuildError,
+    nix::{self, EvalOpts, EvalResult, expr::AttrPath},
+    output::{self, ProjectOutput},
+    project::{LoadedProject, package_attribute},
+};
+
+#[derive(Debug, Deserialize)]
+struct BuildTargets {
+    packages: Vec<String>,
+    shells: Vec<String>,
+}
 
-use crate::util::nix::{self, FixedOutputStoreEntry};
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BuildResult<'a> {
+    attribute: String,
+    r#type: &'a str,
+    name: String,
+    system: &'a str,
+    out_paths: Vec<String>,
+    success: bool,
+}
 
-async fn determine_build_type(
-    attribute: &str,
-    file: &str,
-    entry: FixedOutputStoreEntry,
-) -> (String, String) {
-    let hash = entry.hash;
+#[derive(Debug, Serialize)]
+struct BuildOutput<'a> {
+    project: ProjectOutput,
+    builds: Vec<BuildResult<'a>>,
+}
 
-    let store_path_name = nix::get_store_path_name(&entry.path);
+fn build_type(attribute: &AttrPath) -> &'static str {
+    match attribute.first().unwrap_or_default() {
+        "systems" => "system",
+        "shells" => "shell",
+        "packages" => "package",
+        _ => "unknown attribute",
+    }
+}
 
-    let file_str = entry.path.to_str().unwrap();
+#[derive(Debug, Deserialize)]
+struct ProjectTargets {
+    /// Every system any package or shell of the project is available for.
+    systems: Vec<String>,
+    targets: BTreeMap<String, BuildTargets>,
+}
 
-    let code = format!(
-        "
-	let
-    source = builtins.path {{ path = \"{file_str}\"; sha256 = \"{hash}\"; name = \"{store_path_name}\"; }};
-    project = import \"${{source}}/{file}\";
-	in
-	  project.{attribute}.name
-	");
+/// Lists the systems of the project and the packages and shells that are available for each.
+async fn project_targets(project: &LoadedProject) -> anyhow::Result<ProjectTargets> {
+    let code = project
+        .preamble()
+        .bind(
+            "systemsOf",
+            "kind: builtins.concatMap
+                (value: builtins.attrNames (value.result or { }))
+                (builtins.attrValues (project.${kind} or { }))",
+        )
+        .bind(
+            "systems",
+            "builtins.attrNames (builtins.listToAttrs (map
+                (name: { inherit name; value = null; })
+                (systemsOf \"packages\" ++ systemsOf \"shells\")))",
+        )
+        .bind(
+            "available",
+            "kind: system: builtins.filter
+                (name: project.${kind}.${name}.result ? ${system})
+                (builtins.attrNames (project.${kind} or { }))",
+        )
+        .body(
+            "{
+                inherit systems;
+                targets = builtins.listToAttrs (map (system: {
+                    name = system;
+                    value = {
+                        packages = available \"packages\" system;
+                        shells = available \"shells\" system;
+                    };
+                }) systems);
+            }",
+        );
 
-    let real_name_value = nix::evaluate(
+    let result = nix::evaluate(
         &code,
-        nix::EvalOpts {
+        EvalOpts {
             json: true,
+            raw: false,
             impure: false,
         },
     )
-    .await
-    .unwrap();
-
-    let real_name = match real_name_value {
-        nix::EvalResult::Json(Value::String(s)) => s,
-        _ => attribute.to_string(),
-    };
-
-    let split = attribute.split('.').collect::<Vec<&str>>();
-    let build_type = split[0];
+    .await;
 
-    match build_type {
-        "systems" => ("system".to_string(), real_name),
-        "shells" => ("shell".to_string(), real_name),
-        "packages" => ("package".to_string(), real_name),
-        _ => ("unknown attribute".to_string(), real_name),
+    match result {
+        Ok(EvalResult::Json(value)) => Ok(serde_json::from_value(value)?),
+        Ok(EvalResult::Raw(_)) => bail!("Got raw, expected JSON"),
+        Err(e) => Err(e.context("Failed to get project systems, packages and shells")),
     }
 }
 
@@ -54,75 +106,190 @@ pub async fn build_cmd(
     cli: &nilla_cli_def::Cli,
     args: &nilla_cli_def::commands::build::BuildArgs,
 ) -> anyhow::Result<()> {
-    debug!("Resolving project {}", cli.project);
-    let Ok(project) = crate::util::project::resolve(&cli.project).await else {
-        bail!("Could not find project {}", cli.project);
-    };
+    let mut project = LoadedProject::load(&cli.project).await?;
+    let available = project_targets(&project).await?;
 
-    let entry = project.clone().get_entry();
-    let mut subpath = project.clone().get_subpath();
-    let mut path = project.clone().get_path().join(subpath.clone());
+    let mut names = args.names.clone();
+    let mut systems = args.system.clone();
 
-    debug!("Resolved project {path:?}");
+    // `nilla build <name> <system>` used to take the system as a positional argument.
+    if let [_, system] = names.as_slice()
+        && available.systems.contains(system)
+    {
+        if !systems.is_empty() {
+            bail!(
+                "The system {system} was given as an argument as well as with --system, only use --system"
+            );
+        }
 
-    path.push("nilla.nix");
-    subpath.push("nilla.nix");
+        warn!("Passing the system as an argument is deprecated, use --system {system} instead");
+        systems = names.pop().into_iter().collect();
+    }
 
-    match path.try_exists() {
-        Ok(false) | Err(_) => bail!("File not found"),
-        _ => {}
+    for system in &systems {
+        if !available.systems.contains(system) {
+            bail!(
+                "System {system} is not supported by project {}, expected one of: {}",
+                cli.project,
+                available.systems.join(", ")
+            );
+        }
     }
 
-    let system = match &args.system {
-        Some(s) => s,
-        _ => &match nix::get_system().await {
-            Ok(s) => s,
-            Err(e) => bail!("{e:?}"),
-        },
+    let systems = match systems.as_slice() {
+        [] => vec![project.select_system(None).await?],
+        [system] => vec![project.select_system(Some(system)).await?],
+        systems => systems.to_vec(),
     };
 
-    let attribute = match &args.name {
-        Some(name) => {
-            if name.contains('.') {
-                name
-            } else {
-                &format!("packages.\"{name}\".result.\"{system}\"")
-            }
+    if names.is_empty() && !args.all_packages && !args.all_shells {
+        names.push("default".to_string());
+    }
+
+    let mut targets = vec![];
+    for system in &systems {
+        for name in &names {
+            targets.push((package_attribute(name, system)?, system.as_str()));
         }
-        None => &format!("packages.default.result.\"{system}\""),
-    };
 
-    match nix::exists_in_project(
-        subpath.to_str().unwrap_or("nilla.nix"),
-        entry.clone(),
-        attribute,
-    )
-    .await
-    {
-        Ok(false) => {
-            bail!("Attribute {attribute} does not exist in project {path:?}");
+        let Some(available) = available.targets.get(system) else {
+            continue;
+        };
+        if args.all_packages {
+            targets.extend(available.packages.iter().map(|name| {
+                (
+                    AttrPath::new(["packages", name, "result", system]),
+                    system.as_str(),
+                )
+            }));
+        }
+        if args.all_shells {
+            targets.extend(available.shells.iter().map(|name| {
+                (
+                    AttrPath::new(["shells", name, "result", system]),
+                    system.as_str(),
+                )
+            }));
         }
-        Err(e) => bail!("{e:?}"),
-        _ => {}
     }
+    let mut seen = BTreeSet::new();
+    targets.retain(|(attribute, _)| seen.insert(attribute.to_string()));
 
-    let build_type = determine_build_type(
-        attribute,
-        subpath.to_str().unwrap_or("nilla.nix"),
-        entry.clone(),
-    )
-    .await;
-    info!("Building {} {}", build_type.0, build_type.1);
-    nix::build(
-        &path,
-        attribute,
+    if targets.is_empty() {
+        bail!("Nothing to build in project {}", cli.project);
+    }
+
+    let attributes: Vec<AttrPath> = targets.iter().map(|(a, _)| a.clone()).collect();
+    let infos = project.inspect_all(&attributes, true).await?;
+
+    let missing: Vec<String> = attributes
+        .iter()
+        .zip(&infos)
+        .filter(|(_, info)| !info.exists)
+        .map(|(attribute, _)| attribute.to_string())
+        .collect();
+    if !missing.is_empty() {
+        bail!(
+            "Attribute(s) {} do not exist in project {:?}",
+            missing.join(", "),
+            project.path
+        );
+    }
+
+    let installables: Vec<String> = attributes.iter().map(|a| a.to_string()).collect();
+    for ((attribute, system), info) in targets.iter().zip(&infos) {
+        let name = info.name.as_deref().unwrap_or_default();
+        info!("Building {} {name} for {system}", build_type(attribute));
+    }
+
+    // Nix only takes a single system, builds for any other go to remote builders.
+    let system = match systems.as_slice() {
+        [system] => system.as_str(),
+        _ => "",
+    };
+
+    let out = nix::build_all(
+        &project.path,
+        &installables.iter().map(String::as_str).collect::<Vec<_>>(),
         nix::BuildOpts {
             link: !args.no_link,
             report: true,
             system,
         },
     )
-    .await?;
+    .await;
+
+    // Nix keeps going past failed builds and names every derivation it could not build.
+    let failed = match &out {
+        Ok(_) => None,
+        Err(e) => Some(
+            e.downcast_ref::<BuildError>()
+                .map(|e| e.failed_derivations())
+                .unwrap_or_default(),
+        ),
+    };
 
-    Ok(())
+    let builds: Vec<BuildResult> = targets
+        .into_iter()
+        .zip(infos)
+        .map(|((attribute, system), info)| BuildResult {
+            r#type: build_type(&attribute),
+            name: info.name.unwrap_or_else(|| attribute.to_string()),
+            attribute: attribute.to_string(),
+            system,
+            success: match &failed {
+                None => true,
+                // Without any derivation to blame, the whole build failed.
+                Some(failed) if failed.is_empty() => false,
+                Some(failed) => info
+                    .drv_path
+                    .as_deref()
+                    .is_some_and(|drv| !failed.contains(drv)),
+            },
+            out_paths: info.out_paths,
+        })
+        .collect();
+
+    for build in builds.iter().filter(|b| !b.success) {
+        error!(
+            "Failed to build {} {} for {}",
+            build.r#type, build.name, build.system
+        );
+    }
+
+    let failed = builds.iter().filter(|b| !b.success).count();
+    let total = builds.len();
+
+    if output::is_json(cli) {
+        output::print_json(&BuildOutput {
+            project: ProjectOutput::new(&project),
+            builds,
+        })?;
+    } else if total > 1 {
+        output::print_table(
+            &["Attribute", "Type", "Name", "System", "Result"],
+            builds
+                .into_iter()
+                .map(|build| {
+                    vec![
+                        build.attribute,
+                        build.r#type.to_string(),
+                        build.name,
+                        build.system.to_string(),
+                        match build.success {
+                            true => "built".to_string(),
+                            false => "failed".to_string(),
+                        },
+                    ]
+                })
+                .collect(),
+        );
+    }
+
+    match out {
+        Err(e) if total > 1 => Err(e.context(format!("{failed} of {total} build(s) failed"))),
+        Err(e) => Err(e),
+        Ok(_) if failed > 0 => bail!("{failed} of {total} build(s) failed"),
+        Ok(_) => Ok(()),
+    }
 }
@@ -0,0 +1,36 @@
+use std::path::PathBuf;
+
+use log::info;
+use nilla_cli_def::commands::cache::CacheCommands;
+use serde::Serialize;
+
+use crate::util::{cache, output};
+
+#[derive(Debug, Serialize)]
+struct ClearOutput {
+    directory: PathBuf,
+    removed: usize,
+}
+
+async fn clear_cache(cli: &nilla_cli_def::Cli) -> anyhow::Result<()> {
+    let (directory, removed) = cache::clear()?;
+    info!(
+        "Removed {removed} cached entries from {}",
+        directory.display()
+    );
+
+    if output::is_json(cli) {
+        output::print_json(&ClearOutput { directory, removed })?;
+    }
+
+    Ok(())
+}
+
+pub async fn cache_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::cache::CacheArgs,
+) -> anyhow::Result<()> {
+    match &args.command {
+        CacheCommands::Clear => clear_cache(cli).await,
+    }
+}
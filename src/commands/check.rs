@@ -0,0 +1,221 @@
+use anyhow::bail;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::util::{
+    nix::{self, EvalOpts, EvalResult, expr::AttrPath},
+    output::{self, ProjectOutput},
+    project::LoadedProject,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CheckResult {
+    warnings: Vec<String>,
+    assertions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BuildTargets {
+    packages: Vec<String>,
+    shells: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BuildResult<'a> {
+    r#type: &'a str,
+    name: &'a str,
+    attribute: String,
+    success: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct CheckOutput<'a> {
+    project: ProjectOutput,
+    #[serde(flatten)]
+    check: &'a CheckResult,
+    builds: Vec<BuildResult<'a>>,
+}
+
+pub async fn check_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::check::CheckArgs,
+) -> anyhow::Result<()> {
+    let mut project = LoadedProject::load(&cli.project).await?;
+
+    info!("Checking project {}", cli.project);
+
+    let code = project
+        .preamble()
+        .bind(
+            "failed",
+            "builtins.filter (assertion: !assertion.assertion) (project.assertions or [ ])",
+        )
+        .body(
+            "{
+                warnings = project.warnings or [ ];
+                assertions = builtins.map (assertion: assertion.message) failed;
+            }",
+        );
+
+    let result = nix::evaluate(
+        &code,
+        EvalOpts {
+            json: true,
+            raw: false,
+            impure: false,
+        },
+    )
+    .await;
+
+    let check: CheckResult = match result {
+        Ok(EvalResult::Json(value)) => serde_json::from_value(value)?,
+        Ok(EvalResult::Raw(_)) => bail!("Got raw, expected JSON"),
+        Err(e) => return Err(e.context("Failed to evaluate project checks")),
+    };
+
+    let json = output::is_json(cli);
+
+    for warning in &check.warnings {
+        warn!("{warning}");
+    }
+
+    if !check.assertions.is_empty() {
+        for assertion in &check.assertions {
+            error!("{assertion}");
+        }
+        if json {
+            output::print_json(&CheckOutput {
+                project: ProjectOutput::new(&project),
+                check: &check,
+                builds: vec![],
+            })?;
+        }
+        bail!("{} assertion(s) failed", check.assertions.len());
+    }
+
+    info!(
+        "All assertions passed with {} warning(s)",
+        check.warnings.len()
+    );
+
+    if !args.build {
+        if json {
+            output::print_json(&CheckOutput {
+                project: ProjectOutput::new(&project),
+                check: &check,
+                builds: vec![],
+            })?;
+        }
+        return Ok(());
+    }
+
+    let system = &project.select_system(args.system.as_deref()).await?;
+
+    let code = project
+        .preamble()
+        .bind(
+            "available",
+            "kind: builtins.filter
+                (name: project.${kind}.${name}.result ? ${system})
+                (builtins.attrNames (project.${kind} or { }))",
+        )
+        .body(
+            "{
+                packages = available \"packages\";
+                shells = available \"shells\";
+            }",
+        );
+
+    let result = nix::evaluate(
+        &code,
+        EvalOpts {
+            json: true,
+            raw: false,
+            impure: false,
+        },
+    )
+    .await;
+
+    let targets: BuildTargets = match result {
+        Ok(EvalResult::Json(value)) => serde_json::from_value(value)?,
+        Ok(EvalResult::Raw(_)) => bail!("Got raw, expected JSON"),
+        Err(e) => return Err(e.context("Failed to get project packages and shells")),
+    };
+
+    let attributes = targets
+        .packages
+        .iter()
+        .map(|name| {
+            (
+                "package",
+                name,
+                AttrPath::new(["packages", name, "result", system]).to_string(),
+            )
+        })
+        .chain(targets.shells.iter().map(|name| {
+            (
+                "shell",
+                name,
+                AttrPath::new(["shells", name, "result", system]).to_string(),
+            )
+        }))
+        .collect::<Vec<_>>();
+
+    let mut builds = vec![];
+    let mut build_error = None;
+
+    for (kind, name, attribute) in attributes {
+        info!("Building {kind} {name}");
+        let out = nix::build(
+            &project.path,
+            &attribute,
+            nix::BuildOpts {
+                link: false,
+                report: true,
+                system,
+            },
+        )
+        .await;
+
+        let success = match out {
+            Ok(paths) => !paths.is_empty(),
+            Err(e) => {
+                build_error = Some(e);
+                false
+            }
+        };
+        if !success {
+            error!("Failed to build {kind} {name}");
+        }
+
+        builds.push(BuildResult {
+            r#type: kind,
+            name,
+            attribute,
+            success,
+        });
+    }
+
+    let failed = builds.iter().filter(|b| !b.success).count();
+    let total = builds.len();
+
+    if json {
+        output::print_json(&CheckOutput {
+            project: ProjectOutput::new(&project),
+            check: &check,
+            builds,
+        })?;
+    }
+
+    if failed > 0 {
+        let message = format!("{failed} of {total} build(s) failed");
+        match build_error {
+            Some(e) => return Err(e.context(message)),
+            None => bail!(message),
+        }
+    }
+
+    info!("Built {total} package(s) and shell(s)");
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+use std::path::PathBuf;
+
+use log::info;
+use nilla_cli_def::commands::config::{ConfigCommands, ConfigGetArgs, ConfigSetArgs};
+use serde::Serialize;
+
+use crate::util::{
+    config::{self, Config, KEYS, Origin},
+    output,
+};
+
+#[derive(Debug, Serialize)]
+struct ValueOutput<'a> {
+    key: &'a str,
+    value: Option<String>,
+    /// Where the value was set, or `None` when it is the default.
+    origin: Option<Origin>,
+}
+
+#[derive(Debug, Serialize)]
+struct SetOutput<'a> {
+    file: PathBuf,
+    key: &'a str,
+    value: &'a str,
+}
+
+fn effective_value(config: &Config, key: &config::Key) -> ValueOutput<'static> {
+    match config.get(key.name) {
+        Some((value, origin)) => ValueOutput {
+            key: key.name,
+            value: Some(value.to_string()),
+            origin: Some(origin),
+        },
+        None => ValueOutput {
+            key: key.name,
+            value: key.default_value(),
+            origin: None,
+        },
+    }
+}
+
+async fn list_config(cli: &nilla_cli_def::Cli) -> anyhow::Result<()> {
+    let config = config::config()?;
+    let values: Vec<ValueOutput> = KEYS.iter().map(|k| effective_value(config, k)).collect();
+
+    if output::is_json(cli) {
+        return output::print_json(&values);
+    }
+
+    output::print_table(
+        &["Key", "Value", "Set in", "Description"],
+        KEYS.iter()
+            .zip(values)
+            .map(|(key, value)| {
+                vec![
+                    key.name.to_string(),
+                    value.value.unwrap_or_else(|| "-".to_string()),
+                    value
+                        .origin
+                        .map(|o| o.to_string())
+                        .unwrap_or_else(|| "default".to_string()),
+                    key.description.to_string(),
+                ]
+            })
+            .collect(),
+    );
+
+    Ok(())
+}
+
+async fn get_config(cli: &nilla_cli_def::Cli, args: &ConfigGetArgs) -> anyhow::Result<()> {
+    let key = config::get_key(&args.key)?;
+    let value = effective_value(config::config()?, key);
+
+    if output::is_json(cli) {
+        return output::print_json(&value);
+    }
+
+    if let Some(value) = value.value {
+        println!("{value}");
+    }
+
+    Ok(())
+}
+
+async fn set_config(cli: &nilla_cli_def::Cli, args: &ConfigSetArgs) -> anyhow::Result<()> {
+    let file = if args.local {
+        match config::local_config() {
+            Some(path) => path,
+            None => std::env::current_dir()?.join(config::LOCAL_CONFIG),
+        }
+    } else {
+        config::user_config()?
+    };
+
+    Config::set(&file, &args.key, &args.value)?;
+    info!("Set {} to {} in {}", args.key, args.value, file.display());
+
+    if output::is_json(cli) {
+        output::print_json(&SetOutput {
+            file,
+            key: &args.key,
+            value: &args.value,
+        })?;
+    }
+
+    Ok(())
+}
+
+pub async fn config_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::config::ConfigArgs,
+) -> anyhow::Result<()> {
+    match &args.command {
+        ConfigCommands::List => list_config(cli).await,
+        ConfigCommands::Get(args) => get_config(cli, args).await,
+        ConfigCommands::Set(args) => set_config(cli, args).await,
+    }
+}
@@ -0,0 +1,59 @@
+use serde::Serialize;
+
+use crate::util::{
+    nix::{
+        EvalOpts, EvalResult,
+        expr::{self, AttrPath},
+    },
+    output::{self, ProjectOutput},
+    project::LoadedProject,
+};
+
+#[derive(Debug, Serialize)]
+struct EvalOutput<'a> {
+    project: ProjectOutput,
+    attribute: &'a str,
+    value: serde_json::Value,
+}
+
+pub async fn eval_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::eval::EvalArgs,
+) -> anyhow::Result<()> {
+    let project = LoadedProject::load(&cli.project).await?;
+
+    let attribute = &args.attribute;
+    let selected = expr::select("project", &AttrPath::parse(attribute)?);
+
+    // The applied function is intentionally spliced in as-is since it is Nix code.
+    let value = match &args.apply {
+        Some(apply) => format!("({apply}) ({selected})"),
+        None => selected,
+    };
+
+    let json = output::is_json(cli);
+
+    let result = project
+        .evaluate(
+            &value,
+            EvalOpts {
+                json: args.json || json,
+                raw: args.raw && !json,
+                impure: false,
+            },
+        )
+        .await;
+
+    match result {
+        Ok(EvalResult::Json(value)) if json => output::print_json(&EvalOutput {
+            project: ProjectOutput::new(&project),
+            attribute,
+            value,
+        })?,
+        Ok(EvalResult::Json(value)) => println!("{value}"),
+        Ok(EvalResult::Raw(value)) => println!("{value}"),
+        Err(e) => return Err(e.context(format!("Failed to evaluate {attribute}"))),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use log::info;
+use nilla_cli_def::commands::init::TemplateArgs;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::util::{
+    nix::{self, EvalOpts, EvalResult, expr},
+    output,
+    project::LoadedProject,
+    templates,
+};
+
+#[derive(Debug, Serialize)]
+struct InitOutput<'a> {
+    directory: PathBuf,
+    template: &'a str,
+    from: Option<&'a str>,
+    files: Vec<PathBuf>,
+}
+
+async fn copy_remote_template(
+    uri: &str,
+    dir: &Path,
+    args: &TemplateArgs,
+) -> anyhow::Result<Vec<PathBuf>> {
+    let project = LoadedProject::load(uri).await?;
+    let name = &args.template;
+
+    let code = project
+        .preamble()
+        .bind("name", expr::string(name))
+        .bind("templates", "project.templates or { }")
+        .bind("template", "templates.${name}")
+        .body(
+            "{
+                available = builtins.attrNames templates;
+                path =
+                    if !(templates ? ${name}) then null
+                    else if builtins.isAttrs template then template.path
+                    else template;
+            }",
+        );
+
+    let result = nix::evaluate(
+        &code,
+        EvalOpts {
+            json: true,
+            raw: false,
+            impure: false,
+        },
+    )
+    .await;
+
+    let value = match result {
+        Ok(EvalResult::Json(value)) => value,
+        Ok(EvalResult::Raw(_)) => bail!("Failed to get templates from {uri}"),
+        Err(e) => return Err(e.context(format!("Failed to get templates from {uri}"))),
+    };
+
+    let template_path = match value.get("path") {
+        Some(Value::String(s)) => s.clone(),
+        _ => {
+            let available = value
+                .get("available")
+                .and_then(|a| a.as_array())
+                .map(|a| {
+                    a.iter()
+                        .filter_map(|n| n.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                })
+                .unwrap_or_default();
+
+            if available.is_empty() {
+                bail!("Project {uri} does not expose any templates");
+            }
+            bail!("Template {name} does not exist in {uri}, available templates are: {available}");
+        }
+    };
+
+    let paths = nix::realise(&template_path).await?;
+
+    info!("Copying template {name} from {uri}");
+    let files = templates::copy_template(&paths[0], dir, args.force)?;
+    for file in &files {
+        info!("Created {}", file.display());
+    }
+
+    Ok(files)
+}
+
+pub async fn init_project(
+    cli: &nilla_cli_def::Cli,
+    dir: &Path,
+    args: &TemplateArgs,
+) -> anyhow::Result<()> {
+    let files = match &args.from {
+        Some(uri) => copy_remote_template(uri, dir, args).await?,
+        None => {
+            let template = templates::get_template(&args.template)?;
+            info!(
+                "Using template {} ({})",
+                template.name, template.description
+            );
+            let files = templates::write_template(template, dir, args.force)?;
+            templates::add_pins(template.pins, dir).await?;
+            files
+        }
+    };
+
+    info!("Created Nilla project in {}", dir.display());
+
+    if output::is_json(cli) {
+        output::print_json(&InitOutput {
+            directory: dir.canonicalize()?,
+            template: &args.template,
+            from: args.from.as_deref(),
+            files,
+        })?;
+    }
+
+    Ok(())
+}
+
+pub async fn init_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::init::InitArgs,
+) -> anyhow::Result<()> {
+    let dir = std::env::current_dir()?;
+
+    init_project(cli, &dir, &args.template).await
+}
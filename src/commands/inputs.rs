@@ -0,0 +1,340 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::bail;
+use log::info;
+use nilla_cli_def::commands::inputs::{
+    InputsAddArgs, InputsCommands, InputsRemoveArgs, InputsUpdateArgs,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use crate::util::{
+    nix::{self, EvalOpts, EvalResult},
+    npins,
+    output::{self, ProjectOutput},
+    project::LoadedProject,
+    project::find_local_project,
+};
+
+#[derive(Debug, Serialize)]
+struct InputOutput {
+    r#type: Option<String>,
+    source: String,
+    revision: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ListOutput {
+    project: ProjectOutput,
+    inputs: BTreeMap<String, InputOutput>,
+}
+
+#[derive(Debug, Serialize)]
+struct ChangedPin {
+    name: String,
+    old: Option<String>,
+    new: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct UpdateOutput {
+    directory: PathBuf,
+    updated: Vec<ChangedPin>,
+}
+
+#[derive(Debug, Serialize)]
+struct PinOutput<'a> {
+    directory: PathBuf,
+    name: &'a str,
+    source: Option<&'a str>,
+    revision: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct InputInfo {
+    r#type: Option<String>,
+    repository: Option<Value>,
+    branch: Option<String>,
+    name: Option<String>,
+    url: Option<String>,
+    revision: Option<String>,
+    version: Option<String>,
+}
+
+impl InputInfo {
+    fn source(&self) -> String {
+        let repository = self.repository.as_ref().and_then(|r| {
+            let field = |name: &str| r.get(name).and_then(|v| v.as_str()).unwrap_or_default();
+            match field("type") {
+                "GitHub" => Some(format!("github:{}/{}", field("owner"), field("repo"))),
+                "GitLab" => Some(format!("gitlab:{}", field("repo_path"))),
+                "Forgejo" => {
+                    let server = Url::parse(field("server")).ok()?;
+                    let host = match server.port() {
+                        Some(port) => format!("{}:{port}", server.host_str()?),
+                        None => server.host_str()?.to_string(),
+                    };
+                    Some(format!(
+                        "forgejo:{}/{}?host={host}",
+                        field("owner"),
+                        field("repo")
+                    ))
+                }
+                "Git" => Some(format!("git:{}", field("url"))),
+                _ => None,
+            }
+        });
+
+        let source = match (repository, self.r#type.as_deref(), &self.name, &self.url) {
+            (Some(repository), _, _, _) => repository,
+            (None, Some("Channel"), Some(name), _) => format!("channel:{name}"),
+            (None, _, _, Some(url)) => url.clone(),
+            _ => "-".to_string(),
+        };
+
+        match &self.branch {
+            Some(branch) if source.contains('?') => format!("{source}&ref={branch}"),
+            Some(branch) => format!("{source}?ref={branch}"),
+            None => source,
+        }
+    }
+
+    fn revision(&self) -> Option<String> {
+        self.revision.as_ref().or(self.version.as_ref()).cloned()
+    }
+}
+
+async fn list_inputs(cli: &nilla_cli_def::Cli) -> anyhow::Result<()> {
+    let project = LoadedProject::load(&cli.project).await?;
+
+    // Only the pin metadata is selected here since serializing a whole pin would fetch it.
+    let code = project.preamble()
+        .bind(
+            "describe",
+            "name: input:
+                let
+                    src = input.src or null;
+                    pinned = builtins.isAttrs src;
+                    field = name: if pinned then src.${name} or null else null;
+                in
+                    {
+                        type = field \"type\";
+                        repository = field \"repository\";
+                        branch = field \"branch\";
+                        name = field \"name\";
+                        url = if pinned then src.url or null else if src == null then null else toString src;
+                        revision = field \"revision\";
+                        version = field \"version\";
+                    }",
+        )
+        .body("builtins.mapAttrs describe (project.inputs or { })");
+
+    let result = nix::evaluate(
+        &code,
+        EvalOpts {
+            json: true,
+            raw: false,
+            impure: false,
+        },
+    )
+    .await;
+
+    let inputs: BTreeMap<String, InputInfo> = match result {
+        Ok(EvalResult::Json(value)) => serde_json::from_value(value)?,
+        Ok(EvalResult::Raw(_)) => bail!("Got raw, expected JSON"),
+        Err(e) => return Err(e.context("Failed to get project inputs")),
+    };
+
+    if output::is_json(cli) {
+        return output::print_json(&ListOutput {
+            project: ProjectOutput::new(&project),
+            inputs: inputs
+                .into_iter()
+                .map(|(name, input)| {
+                    let output = InputOutput {
+                        r#type: input.r#type.clone(),
+                        source: input.source(),
+                        revision: input.revision(),
+                    };
+                    (name, output)
+                })
+                .collect(),
+        });
+    }
+
+    if inputs.is_empty() {
+        info!("Project {} has no inputs", cli.project);
+        return Ok(());
+    }
+
+    info!("Showing inputs of {}", cli.project);
+    println!();
+
+    output::print_table(
+        &["Name", "Type", "Source", "Revision"],
+        inputs
+            .iter()
+            .map(|(name, input)| {
+                vec![
+                    name.clone(),
+                    input.r#type.clone().unwrap_or("-".to_string()),
+                    input.source(),
+                    input.revision().unwrap_or("-".to_string()),
+                ]
+            })
+            .collect(),
+    );
+
+    Ok(())
+}
+
+async fn update_inputs(cli: &nilla_cli_def::Cli, args: &InputsUpdateArgs) -> anyhow::Result<()> {
+    npins::ensure_available()?;
+    let dir = find_local_project(&cli.project)?;
+    let before = npins::read_revisions(&dir)?;
+
+    for name in &args.names {
+        if !before.contains_key(name) {
+            bail!("Pin {name} does not exist in {}", dir.display());
+        }
+    }
+
+    if args.names.is_empty() {
+        info!("Updating all pins in {}", dir.display());
+    } else {
+        info!("Updating {}", args.names.join(", "));
+    }
+
+    let mut npins_args = vec!["update"];
+    npins_args.extend(args.names.iter().map(|n| n.as_str()));
+    npins::run(&dir, &npins_args).await?;
+
+    let after = npins::read_revisions(&dir)?;
+
+    let changed = after
+        .into_iter()
+        .filter_map(|(name, revision)| {
+            let previous = before.get(&name).cloned().flatten();
+            if previous == revision {
+                return None;
+            }
+            Some(ChangedPin {
+                name,
+                old: previous,
+                new: revision,
+            })
+        })
+        .collect::<Vec<ChangedPin>>();
+
+    if output::is_json(cli) {
+        return output::print_json(&UpdateOutput {
+            directory: dir,
+            updated: changed,
+        });
+    }
+
+    if changed.is_empty() {
+        info!("All pins are up to date");
+        return Ok(());
+    }
+
+    info!("Updated {} pin(s)", changed.len());
+    println!();
+    output::print_table(
+        &["Name", "Old", "New"],
+        changed
+            .into_iter()
+            .map(|pin| {
+                vec![
+                    pin.name,
+                    pin.old.unwrap_or("-".to_string()),
+                    pin.new.unwrap_or("-".to_string()),
+                ]
+            })
+            .collect(),
+    );
+
+    Ok(())
+}
+
+async fn add_input(cli: &nilla_cli_def::Cli, args: &InputsAddArgs) -> anyhow::Result<()> {
+    npins::ensure_available()?;
+    let dir = find_local_project(&cli.project)?;
+
+    if npins::read_revisions(&dir)
+        .map(|pins| pins.contains_key(&args.name))
+        .unwrap_or(false)
+    {
+        bail!("Pin {} already exists in {}", args.name, dir.display());
+    }
+
+    if !dir.join("npins").exists() {
+        npins::run(&dir, &["init", "--bare"]).await?;
+    }
+
+    let npins_args = npins::add_args(&args.name, &args.source)?;
+    info!("Pinning {} from {}", args.name, args.source);
+    npins::run(
+        &dir,
+        &npins_args.iter().map(|a| a.as_str()).collect::<Vec<&str>>(),
+    )
+    .await?;
+
+    let revision = npins::read_revisions(&dir)?.remove(&args.name).flatten();
+    info!(
+        "Pinned {} at {}",
+        args.name,
+        revision.as_deref().unwrap_or("-")
+    );
+    info!(
+        "Use it in your project with `inputs.{}.src = pins.{};`",
+        args.name, args.name
+    );
+
+    if output::is_json(cli) {
+        output::print_json(&PinOutput {
+            directory: dir,
+            name: &args.name,
+            source: Some(&args.source),
+            revision,
+        })?;
+    }
+
+    Ok(())
+}
+
+async fn remove_input(cli: &nilla_cli_def::Cli, args: &InputsRemoveArgs) -> anyhow::Result<()> {
+    npins::ensure_available()?;
+    let dir = find_local_project(&cli.project)?;
+
+    if !npins::read_revisions(&dir)?.contains_key(&args.name) {
+        bail!("Pin {} does not exist in {}", args.name, dir.display());
+    }
+
+    npins::run(&dir, &["remove", &args.name]).await?;
+    info!("Removed pin {}", args.name);
+
+    if output::is_json(cli) {
+        output::print_json(&PinOutput {
+            directory: dir,
+            name: &args.name,
+            source: None,
+            revision: None,
+        })?;
+    }
+
+    Ok(())
+}
+
+pub async fn inputs_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::inputs::InputsArgs,
+) -> anyhow::Result<()> {
+    match &args.command {
+        InputsCommands::List => list_inputs(cli).await,
+        InputsCommands::Update(args) => update_inputs(cli, args).await,
+        InputsCommands::Add(args) => add_input(cli, args).await,
+        InputsCommands::Remove(args) => remove_input(cli, args).await,
+    }
+}
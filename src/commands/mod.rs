@@ -1,4 +1,13 @@
 pub mod build;
+pub mod cache;
+pub mod check;
+pub mod config;
+pub mod eval;
+pub mod init;
+pub mod inputs;
+pub mod new;
+pub mod registry;
+pub mod repl;
 pub mod run;
 pub mod shell;
 pub mod show;
@@ -0,0 +1,21 @@
+use anyhow::bail;
+use log::debug;
+
+use super::init::init_project;
+
+pub async fn new_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::new::NewArgs,
+) -> anyhow::Result<()> {
+    if args.path.exists() && args.path.read_dir()?.next().is_some() && !args.template.force {
+        bail!(
+            "Directory {} is not empty, use --force to create a project in it anyway",
+            args.path.display()
+        );
+    }
+
+    debug!("Creating directory {:?}", args.path);
+    std::fs::create_dir_all(&args.path)?;
+
+    init_project(cli, &args.path, &args.template).await
+}
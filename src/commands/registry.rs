@@ -0,0 +1,178 @@
+use std::{collections::BTreeMap, path::PathBuf};
+
+use anyhow::bail;
+use log::info;
+use nilla_cli_def::commands::registry::{
+    RegistryAddArgs, RegistryCommands, RegistryPinArgs, RegistryRemoveArgs,
+};
+use serde::Serialize;
+use url::form_urlencoded;
+
+use crate::util::{
+    output,
+    project::{
+        self, GitInfo, GitXInfo, Source,
+        schemes::{SchemeRegistry, SourceSpec, TarballScheme},
+    },
+    registry::{Origin, Registry},
+};
+
+#[derive(Debug, Serialize)]
+struct EntryOutput<'a> {
+    source: &'a str,
+    origin: Origin,
+}
+
+#[derive(Debug, Serialize)]
+struct ChangedEntry<'a> {
+    registry: PathBuf,
+    name: &'a str,
+    source: Option<&'a str>,
+}
+
+/// Replaces query parameters of a project URI, keeping all others as they are.
+fn set_query_param(uri: &str, remove: &[&str], key: &str, value: &str) -> String {
+    let (base, query) = uri.split_once('?').unwrap_or((uri, ""));
+
+    let mut serializer = form_urlencoded::Serializer::new(String::new());
+    for (k, v) in form_urlencoded::parse(query.as_bytes()) {
+        if k != key && !remove.contains(&k.as_ref()) {
+            serializer.append_pair(&k, &v);
+        }
+    }
+    serializer.append_pair(key, value);
+
+    format!("{base}?{}", serializer.finish())
+}
+
+async fn list_entries(cli: &nilla_cli_def::Cli) -> anyhow::Result<()> {
+    let registry = Registry::load()?;
+    let entries = registry.entries();
+
+    if output::is_json(cli) {
+        let entries: BTreeMap<&str, EntryOutput> = entries
+            .into_iter()
+            .map(|(name, (source, origin))| (name, EntryOutput { source, origin }))
+            .collect();
+        return output::print_json(&entries);
+    }
+
+    if entries.is_empty() {
+        info!("The registry is empty");
+        return Ok(());
+    }
+
+    output::print_table(
+        &["Name", "Source", "Registry"],
+        entries
+            .into_iter()
+            .map(|(name, (source, origin))| {
+                vec![name.to_string(), source.to_string(), origin.to_string()]
+            })
+            .collect(),
+    );
+
+    Ok(())
+}
+
+async fn add_entry(cli: &nilla_cli_def::Cli, args: &RegistryAddArgs) -> anyhow::Result<()> {
+    if let Err(e) = SchemeRegistry::default().parse(&args.source) {
+        bail!("Invalid source {}: {e}", args.source);
+    }
+
+    let mut registry = Registry::load()?;
+    registry.add(&args.name, &args.source)?;
+    info!("Added {} as {}", args.name, args.source);
+
+    if output::is_json(cli) {
+        output::print_json(&ChangedEntry {
+            registry: registry.user_path().to_path_buf(),
+            name: &args.name,
+            source: Some(&args.source),
+        })?;
+    }
+
+    Ok(())
+}
+
+async fn remove_entry(cli: &nilla_cli_def::Cli, args: &RegistryRemoveArgs) -> anyhow::Result<()> {
+    let mut registry = Registry::load()?;
+    registry.remove(&args.name)?;
+    info!("Removed {}", args.name);
+
+    if output::is_json(cli) {
+        output::print_json(&ChangedEntry {
+            registry: registry.user_path().to_path_buf(),
+            name: &args.name,
+            source: None,
+        })?;
+    }
+
+    Ok(())
+}
+
+async fn pin_entry(cli: &nilla_cli_def::Cli, args: &RegistryPinArgs) -> anyhow::Result<()> {
+    let mut registry = Registry::load()?;
+
+    let Some((target, _)) = registry.get(&args.name) else {
+        bail!("{} is not in the registry", args.name);
+    };
+    let target = target.to_string();
+
+    let schemes = SchemeRegistry::default();
+    let is_tarball = schemes.find(&target).is_some_and(|s| s.name() == "tarball");
+
+    let pinned = match (schemes.parse(&target)?, &args.rev) {
+        (SourceSpec::Path { .. }, _) => bail!("Local paths cannot be pinned"),
+        (SourceSpec::Tarball(_), Some(_)) if is_tarball && !TarballScheme::is_archive(&target) => {
+            bail!("Tarball URLs cannot be pinned to a revision")
+        }
+        // Archives take either a rev or a ref, and their hash changes along with the revision.
+        (SourceSpec::Tarball(_), Some(rev)) if is_tarball => {
+            set_query_param(&target, &["ref", "narHash", "sha256"], "rev", rev)
+        }
+        (_, Some(rev)) => set_query_param(&target, &[], "rev", rev),
+        (_, None) => match project::resolve(&target).await? {
+            Source::Git {
+                info: GitInfo { rev: Some(rev), .. },
+                ..
+            }
+            | Source::Sourcehut {
+                info: GitXInfo { rev: Some(rev), .. },
+                ..
+            } => set_query_param(&target, &[], "rev", &rev),
+            Source::Tarball { entry, .. } if is_tarball => {
+                set_query_param(&target, &["narHash"], "sha256", &entry.hash)
+            }
+            // Forge archives are only fetched when the source already has a rev.
+            Source::Tarball { .. } => target.clone(),
+            Source::Path { .. } => bail!("Local paths cannot be pinned"),
+            _ => bail!("Could not determine the revision of {target}"),
+        },
+    };
+
+    registry.add(&args.name, &pinned)?;
+    info!("Pinned {} to {pinned}", args.name);
+
+    if output::is_json(cli) {
+        output::print_json(&ChangedEntry {
+            registry: registry.user_path().to_path_buf(),
+            name: &args.name,
+            source: Some(&pinned),
+        })?;
+    }
+
+    Ok(())
+}
+
+pub async fn registry_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::registry::RegistryArgs,
+) -> anyhow::Result<()> {
+    match &args.command {
+        RegistryCommands::List => list_entries(cli).await,
+        RegistryCommands::Add(args) => add_entry(cli, args).await,
+        RegistryCommands::Remove(args) => remove_entry(cli, args).await,
+        RegistryCommands::Pin(args) => pin_entry(cli, args).await,
+    }
+}
@@ -0,0 +1,26 @@
+use anyhow::bail;
+use log::info;
+
+use crate::util::{nix, output, project::LoadedProject};
+
+pub async fn repl_cmd(
+    cli: &nilla_cli_def::Cli,
+    args: &nilla_cli_def::commands::repl::ReplArgs,
+) -> anyhow::Result<()> {
+    if output::is_json(cli) {
+        bail!("The repl command does not support JSON output");
+    }
+
+    let mut project = LoadedProject::load(&cli.project).await?;
+    project.select_system(args.system.as_deref()).await?;
+
+    let code = project
+        .preamble()
+        .body("{ inherit source project system; }");
+
+    info!("Starting REPL for {}", cli.project);
+    info!("Loaded variables: project, source, system");
+    nix::repl(&code);
+
+    Ok(())
+}
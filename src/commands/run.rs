@@ -2,74 +2,65 @@ use std::path::PathBuf;
 
 use anyhow::bail;
 use log::{debug, info, trace};
-
-use crate::util::nix;
+use serde::Serialize;
+
+use crate::util::{
+    nix::{self, expr::AttrPath},
+    output::{self, ProjectOutput},
+    project::{LoadedProject, package_attribute},
+};
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RunOutput<'a> {
+    project: ProjectOutput,
+    attribute: String,
+    name: &'a str,
+    system: &'a str,
+    out_paths: &'a [String],
+    program: PathBuf,
+    args: &'a [String],
+}
 
 pub async fn run_cmd(
     cli: &nilla_cli_def::Cli,
     args: &nilla_cli_def::commands::run::RunArgs,
 ) -> anyhow::Result<()> {
-    debug!("Resolving project {}", cli.project);
-    let rs = crate::util::project::resolve(&cli.project).await;
-
-    let Ok(project) = rs else {
-        bail!("{:?}", rs.unwrap_err());
-    };
+    let mut project = LoadedProject::load(&cli.project).await?;
 
-    let entry = project.clone().get_entry();
-    let mut subpath = project.clone().get_subpath();
-    let mut path = project.clone().get_path().join(subpath.clone());
-
-    debug!("Resolved project {path:?}");
-
-    path.push("nilla.nix");
-    subpath.push("nilla.nix");
-
-    match path.try_exists() {
-        Ok(false) | Err(_) => bail!("File not found"),
-        _ => {}
-    }
-
-    let system = match &args.system {
-        Some(s) => s,
-        _ => &match nix::get_system().await {
-            Ok(s) => s,
-            Err(e) => bail!("{e:?}"),
-        },
-    };
+    let system = &project.select_system(args.system.as_deref()).await?;
 
     let (attribute, name) = match &args.name {
         Some(name) => {
-            if name.contains('.') {
-                let sp = name.split('.').collect::<Vec<&str>>();
-                (name, sp[1])
-            } else {
-                (
-                    &format!("packages.\"{name}\".result.\"{system}\""),
-                    name.as_str(),
-                )
-            }
+            let attribute = package_attribute(name, system)?;
+            let name = match name.contains('.') {
+                true => match attribute.segments().get(1) {
+                    Some(name) => name.clone(),
+                    None => bail!("Could not get package name from {attribute}"),
+                },
+                false => name.clone(),
+            };
+            (attribute, name)
         }
-        None => (&format!("packages.default.result.\"{system}\""), "default"),
+        None => (
+            AttrPath::new(["packages", "default", "result", system]),
+            "default".to_string(),
+        ),
     };
-
-    match nix::exists_in_project(
-        subpath.to_str().unwrap_or("nilla.nix"),
-        entry.clone(),
-        attribute,
-    )
-    .await
-    {
-        Ok(false) => {
-            bail!("Attribute {attribute} does not exist in project {path:?}");
-        }
-        Err(e) => bail!("{e:?}"),
-        _ => {}
+    let name = name.as_str();
+
+    let info = project.inspect(&attribute, false).await?;
+    if !info.exists {
+        bail!(
+            "Attribute {attribute} does not exist in project {:?}",
+            project.path
+        );
     }
+
     info!("Building package {name}");
     let out = nix::build(
-        &path,
-        attribute,
+        &project.path,
+        &attribute.to_string(),
         nix::BuildOpts {
             link: false,
             report: true,
@@ -78,38 +69,38 @@ pub async fn run_cmd(
     )
     .await;
 
-    let Ok(value) = out else {
-        bail!("{:?}", out.unwrap_err());
-    };
+    let value = out?;
 
     if value.is_empty() {
         bail!("Package has no outputs");
     }
 
-    let main_prog = nix::get_main_program(
-        path.iter().next_back().unwrap().to_str().unwrap(),
-        entry.clone(),
-        name,
-        nix::GetMainProgramOpts { system },
-    )
-    .await;
-
-    let Ok(main) = main_prog else {
-        bail!("{:?}", main_prog.err());
-    };
+    let main = info.main_program.as_deref().unwrap_or(name);
 
     let mut binary_path = PathBuf::from(value[0].clone());
     binary_path.push("bin");
     binary_path.push(main);
-    trace!("Binary path: {:?}", path.as_os_str());
+    trace!("Binary path: {:?}", binary_path.as_os_str());
     info!("Running Package {name}");
 
     let command_args = &args.remaining;
     debug!("With args: {}", command_args.join(" "));
-    cargo_util::ProcessBuilder::new(binary_path)
+
+    // The command takes over stdout, so the result goes to stderr to keep both parseable.
+    if output::is_json(cli) {
+        output::eprint_json(&RunOutput {
+            project: ProjectOutput::new(&project),
+            attribute: attribute.to_string(),
+            name,
+            system,
+            out_paths: &value,
+            program: binary_path.clone(),
+            args: command_args,
+        })?;
+    }
+
+    cargo_util::ProcessBuilder::new(&binary_path)
         .args(command_args)
         .exec_replace()
-        .unwrap();
-
-    Ok(())
+        .map_err(|e| e.context(format!("Failed to run {}", binary_path.display())))
 }
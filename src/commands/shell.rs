@@ -1,38 +1,29 @@
 use anyhow::bail;
-use log::{debug, info};
+use log::info;
+use serde::Serialize;
 
-use crate::util::nix::{self, ShellOpts};
+use crate::util::{
+    nix::{self, ShellOpts, expr::AttrPath},
+    output::{self, ProjectOutput},
+    project::LoadedProject,
+};
+
+#[derive(Debug, Serialize)]
+struct ShellOutput<'a> {
+    project: ProjectOutput,
+    attribute: String,
+    name: &'a str,
+    system: &'a str,
+    command: &'a str,
+}
 
 pub async fn shell_cmd(
     cli: &nilla_cli_def::Cli,
     args: &nilla_cli_def::commands::shell::ShellArgs,
 ) -> anyhow::Result<()> {
-    debug!("Resolving project {}", cli.project);
-    let Ok(project) = crate::util::project::resolve(&cli.project).await else {
-        bail!("Could not find project {}", cli.project);
-    };
-
-    let entry = project.clone().get_entry();
-    let mut subpath = project.clone().get_subpath();
-    let mut path = project.clone().get_path().join(subpath.clone());
+    let mut project = LoadedProject::load(&cli.project).await?;
 
-    debug!("Resolved project {path:?}");
-
-    path.push("nilla.nix");
-    subpath.push("nilla.nix");
-
-    match path.try_exists() {
-        Ok(false) | Err(_) => bail!("File not found"),
-        _ => {}
-    }
-
-    let system = match &args.system {
-        Some(s) => s,
-        _ => &match nix::get_system().await {
-            Ok(s) => s,
-            Err(e) => bail!("{e:?}"),
-        },
-    };
+    let system = &project.select_system(args.system.as_deref()).await?;
 
     let command = match &args.command {
         Some(c) => c,
@@ -42,24 +33,32 @@ pub async fn shell_cmd(
         },
     };
 
-    let attribute = format!("shells.\"{}\".result.\"{system}\"", args.name);
+    let attribute = AttrPath::new(["shells", &args.name, "result", system]);
+
+    if !project.inspect(&attribute, false).await?.exists {
+        bail!(
+            "Shell {attribute} does not exist in project {:?}",
+            project.path
+        );
+    }
 
-    match nix::exists_in_project(
-        subpath.to_str().unwrap_or("nilla.nix"),
-        entry.clone(),
-        &attribute,
-    )
-    .await
-    {
-        Ok(false) => {
-            bail!("Shell {attribute} does not exist in project {path:?}");
-        }
-        Err(e) => bail!("{e:?}"),
-        _ => {}
+    // The command takes over stdout, so the result goes to stderr to keep both parseable.
+    if output::is_json(cli) {
+        output::eprint_json(&ShellOutput {
+            project: ProjectOutput::new(&project),
+            attribute: attribute.to_string(),
+            name: &args.name,
+            system,
+            command,
+        })?;
     }
 
     info!("Entering shell {}", args.name);
-    nix::shell(&path, &attribute, ShellOpts { system, command });
+    nix::shell(
+        &project.path,
+        &attribute.to_string(),
+        ShellOpts { system, command },
+    );
 
     Ok(())
 }
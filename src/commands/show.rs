@@ -7,7 +7,11 @@ use prettytable::{Attr, Cell, Row, Table, format};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::util::nix::{self, EvalOpts, EvalResult, FixedOutputStoreEntry};
+use crate::util::{
+    nix::{self, EvalOpts, EvalResult, expr},
+    output::{self, ProjectOutput},
+    project::{LoadedProject, PathInfo, Source},
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 struct ExplainEntryData {
@@ -25,6 +29,12 @@ struct ExplainEntry {
     entries: Vec<ExplainEntry>,
 }
 
+#[derive(Debug, Serialize)]
+struct ShowOutput {
+    project: ProjectOutput,
+    entries: Vec<ExplainEntry>,
+}
+
 fn show_entry(entry: ExplainEntry) {
     let name = format!(" {} ", entry.name);
     println!("{}", name.black().on_white().bold());
@@ -86,35 +96,77 @@ fn show_entry(entry: ExplainEntry) {
     println!();
 }
 
-async fn show_attribute(file: &str, entry: FixedOutputStoreEntry, attribute: &str) {
-    trace!("Getting explain entry for {attribute}");
+/// Describes the working tree a local git project was loaded from.
+fn source_entry(project: &LoadedProject) -> Option<ExplainEntry> {
+    let Source::Path {
+        info: PathInfo {
+            git: Some(status), ..
+        },
+        ..
+    } = &project.source
+    else {
+        return None;
+    };
 
-    let file_str = entry.path.to_str().unwrap();
+    let mut rows = vec![
+        vec!["Layout".to_string(), status.layout.to_string()],
+        vec![
+            "Revision".to_string(),
+            status.rev.clone().unwrap_or("no commits".into()),
+        ],
+        vec![
+            "Branch".to_string(),
+            status.branch.clone().unwrap_or("detached".into()),
+        ],
+        vec![
+            "Status".to_string(),
+            match status.dirty {
+                true => "dirty".to_string(),
+                false => "clean".to_string(),
+            },
+        ],
+    ];
+    rows.extend(
+        status
+            .modified
+            .iter()
+            .map(|file| vec!["Modified".to_string(), file.display().to_string()]),
+    );
+
+    Some(ExplainEntry {
+        name: "Source".to_string(),
+        description: String::new(),
+        data: ExplainEntryData {
+            columns: vec!["Property".to_string(), "Value".to_string()],
+            rows,
+        },
+        entries: vec![],
+    })
+}
 
-    let hash = entry.hash;
+async fn get_attribute(
+    project: &LoadedProject,
+    attribute: &str,
+) -> anyhow::Result<Option<ExplainEntry>> {
+    trace!("Getting explain entry for {attribute}");
 
-    let store_path_name = nix::get_store_path_name(&entry.path);
+    let code = project
+        .preamble()
+        .bind("attribute", expr::string(attribute))
+        .body("project.explain.${attribute}.result or null");
 
     let raw_entry = nix::evaluate(
-        &format!(
-            "
-    let
-        source = builtins.path {{ path = \"{file_str}\"; sha256 = \"{hash}\"; name = \"{store_path_name}\"; }};
-        project = import \"${{source}}/{file}\";
-        attribute = \"{attribute}\";
-    in
-        project.explain.\"${{attribute}}\".result or null
-        "
-        ),
+        &code,
         EvalOpts {
             json: true,
             impure: false,
+            raw: false,
         },
     )
     .await;
 
     match raw_entry {
-        Ok(EvalResult::Json(Value::Null)) => {}
+        Ok(EvalResult::Json(Value::Null)) => Ok(None),
         Ok(EvalResult::Json(value)) => {
             let serialized = value.to_string();
 
@@ -122,110 +174,89 @@ async fn show_attribute(file: &str, entry: FixedOutputStoreEntry, attribute: &st
                 Ok(e) => e,
                 Err(e) => {
                     error!("Failed to parse explain entry for {attribute}: {e}");
-                    return;
+                    return Ok(None);
                 }
             };
 
             trace!("Got explain entry for {attribute}: {entry:?}");
-            show_entry(entry);
+            Ok(Some(entry))
         }
-        _ => {
-            error!("Failed to get explain entry for {attribute}");
-        }
-    };
+        Ok(EvalResult::Raw(_)) => bail!("Got raw, expected JSON"),
+        Err(e) => Err(e.context(format!("Failed to get explain entry for {attribute}"))),
+    }
 }
 
 pub async fn show_cmd(
     cli: &nilla_cli_def::Cli,
     args: &nilla_cli_def::commands::show::ShowArgs,
 ) -> anyhow::Result<()> {
-    // TODO: Look into refactoring this section out, it's used in many places over the codebase and could be cleaner
-    debug!("Resolving project {}", cli.project);
-    let Ok(project) = crate::util::project::resolve(&cli.project).await else {
-        bail!("Could not find project {}", cli.project);
-    };
-
-    let entry = project.clone().get_entry();
-    let subpath = project.clone().get_subpath();
-    let mut path = project.get_path().join(subpath.clone());
-
-    debug!("Resolved project {path:?}");
+    let project = LoadedProject::load(&cli.project).await?;
 
-    path.push("nilla.nix");
-
-    match path.try_exists() {
-        Ok(false) | Err(_) => bail!("File not found"),
-        _ => {}
-    }
-
-    let file_str = entry.path.to_str().unwrap();
-
-    let hash = entry.clone().hash;
-
-    let store_path_name = nix::get_store_path_name(&entry.path);
-    // TODO: END
+    let json = output::is_json(cli);
+    let mut entries = vec![];
 
     match &args.name {
         Some(name) => {
+            let code = project
+                .preamble()
+                .bind("attribute", expr::string(name))
+                .body("project.explain ? ${attribute}");
+
             let has_explainer = nix::evaluate(
-                &format!(
-                    "
-    let
-        source = builtins.path {{ path = \"{file_str}\"; sha256 = \"{hash}\"; name = \"{store_path_name}\"; }};
-        project = import \"${{source}}/nilla.nix\";
-        attribute = \"{name}\";
-    in
-        project.explain ? ${{attribute}}
-        "
-                ),
+                &code,
                 EvalOpts {
                     json: true,
                     impure: false,
+                    raw: false,
                 },
             )
             .await;
 
             match has_explainer {
                 Ok(EvalResult::Json(Value::Bool(true))) => {
-                    info!("Showing information about {} in {}", name, cli.project);
-                    println!();
-                    show_attribute("nilla.nix", entry.clone(), name.as_str()).await;
+                    if !json {
+                        info!("Showing information about {} in {}", name, cli.project);
+                        println!();
+                    }
+                    entries.extend(get_attribute(&project, name.as_str()).await?);
                 }
                 Ok(EvalResult::Json(Value::Bool(false))) => {
                     info!("No information available for {name}");
                 }
-                _ => {
-                    error!("Failed to get info for {name}");
-                }
+                Ok(_) => bail!("Failed to get info for {name}"),
+                Err(e) => return Err(e.context(format!("Failed to get info for {name}"))),
             }
         }
         None => {
             debug!("Evaluating project");
-            info!("Showing information about {}", cli.project);
-            println!();
+            if !json {
+                info!("Showing information about {}", cli.project);
+                println!();
+                entries.extend(source_entry(&project));
+            }
 
-            let store_path_name = nix::get_store_path_name(&entry.path);
+            let code = project
+                .preamble()
+                .bind(
+                    "reserved",
+                    expr::string_list(["assertions", "warnings", "extend", "explain"]),
+                )
+                .body("builtins.attrNames (builtins.removeAttrs project reserved)");
 
             let names_result = nix::evaluate(
-                &format!(
-                    "
-    let
-        source = builtins.path {{ path = \"{file_str}\"; sha256 = \"{hash}\"; name = \"{store_path_name}\"; }};
-        project = import \"${{source}}/nilla.nix\";
-        reserved = [ \"assertions\" \"warnings\" \"extend\" \"explain\" ];
-    in
-        builtins.attrNames (builtins.removeAttrs project reserved)
-        "
-                ),
+                &code,
                 EvalOpts {
                     json: true,
                     impure: false,
+                    raw: false,
                 },
             )
             .await;
 
-            let Ok(EvalResult::Json(names)) = names_result else {
-                bail!("Failed to get Nilla project attributes");
+            let names = match names_result {
+                Ok(EvalResult::Json(names)) => names,
+                Ok(EvalResult::Raw(_)) => bail!("Failed to get Nilla project attributes"),
+                Err(e) => return Err(e.context("Failed to get Nilla project attributes")),
             };
 
             let Some(names_vec) = names.as_array() else {
@@ -241,10 +272,21 @@ pub async fn show_cmd(
             debug!("Got all names {str_names:?}");
 
             for name in str_names {
-                show_attribute("nilla.nix", entry.clone(), name).await;
+                entries.extend(get_attribute(&project, name).await?);
             }
         }
     };
 
+    if json {
+        output::print_json(&ShowOutput {
+            project: ProjectOutput::new(&project),
+            entries,
+        })?;
+    } else {
+        for entry in entries {
+            show_entry(entry);
+        }
+    }
+
     Ok(())
 }
@@ -1,12 +1,12 @@
 use anyhow::bail;
 use clap::{
-    CommandFactory, Parser,
+    CommandFactory,
     builder::styling::{AnsiColor, Color::Ansi, Style},
 };
 use fern::colors::{Color, ColoredLevelConfig};
-use log::{LevelFilter, debug, error, trace};
+use log::{LevelFilter, debug, error, trace, warn};
+use nilla::util::{config, errors};
 use nilla_cli_def::{Cli, Commands, commands::completions};
-use serde::de::value::UsizeDeserializer;
 
 const B: Style = Style::new().bold();
 const D: Style = Style::new().dimmed();
@@ -21,7 +21,7 @@ async fn main() -> anyhow::Result<()> {
         .warn(Color::Yellow)
         .error(Color::Red);
 
-    let cli = Cli::parse();
+    let cli = config::load_cli()?;
     let mut filter_level = match cli.verbose {
         0 => LevelFilter::Info,
         1 => LevelFilter::Debug,
@@ -56,25 +56,40 @@ async fn main() -> anyhow::Result<()> {
                 .chain(std::io::stderr()),
         )
         .apply()?;
+
+    // Problems in the config are only reported now that logging is set up.
+    for warning in config::config()?.warnings() {
+        warn!("{warning}");
+    }
+
     let result = run_cli(cli).await;
     match result {
         Ok(c) => std::process::exit(c.unwrap_or(0)),
         Err(e) => {
-            error!("{e}");
-            std::process::exit(1);
+            error!("{e:#}");
+            std::process::exit(errors::exit_code(&e));
         }
     }
 }
 
-async fn run_cli(cli: Cli) -> anyhow::Result<Option<i32>> {
+async fn run_cli(cli: &Cli) -> anyhow::Result<Option<i32>> {
     trace!("Running {:?}", cli.command);
 
     match &cli.command {
         Some(command) => match command {
-            Commands::Show(args) => nilla::commands::show::show_cmd(&cli, args).await?,
-            Commands::Shell(args) => nilla::commands::shell::shell_cmd(&cli, args).await?,
-            Commands::Run(args) => nilla::commands::run::run_cmd(&cli, args).await?,
-            Commands::Build(args) => nilla::commands::build::build_cmd(&cli, args).await?,
+            Commands::Show(args) => nilla::commands::show::show_cmd(cli, args).await?,
+            Commands::Shell(args) => nilla::commands::shell::shell_cmd(cli, args).await?,
+            Commands::Run(args) => nilla::commands::run::run_cmd(cli, args).await?,
+            Commands::Build(args) => nilla::commands::build::build_cmd(cli, args).await?,
+            Commands::Check(args) => nilla::commands::check::check_cmd(cli, args).await?,
+            Commands::Eval(args) => nilla::commands::eval::eval_cmd(cli, args).await?,
+            Commands::Repl(args) => nilla::commands::repl::repl_cmd(cli, args).await?,
+            Commands::Init(args) => nilla::commands::init::init_cmd(cli, args).await?,
+            Commands::New(args) => nilla::commands::new::new_cmd(cli, args).await?,
+            Commands::Inputs(args) => nilla::commands::inputs::inputs_cmd(cli, args).await?,
+            Commands::Cache(args) => nilla::commands::cache::cache_cmd(cli, args).await?,
+            Commands::Registry(args) => nilla::commands::registry::registry_cmd(cli, args).await?,
+            Commands::Config(args) => nilla::commands::config::config_cmd(cli, args).await?,
             Commands::Completions(args) => completions::completions_cmd(args, &mut Cli::command()),
             Commands::External(items) => {
                 debug!("got external subcommand: {items:?}");
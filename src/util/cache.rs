@@ -0,0 +1,180 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use log::{debug, trace};
+use sha2::{Digest, Sha256};
+
+/// Gets the directory Nilla stores cached results in, `$XDG_CACHE_HOME/nilla` or
+/// `~/.cache/nilla` when that is not set.
+pub fn cache_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CACHE_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("nilla"));
+    }
+
+    match std::env::var_os("HOME").filter(|d| !d.is_empty()) {
+        Some(home) => Ok(PathBuf::from(home).join(".cache").join("nilla")),
+        None => {
+            bail!("Could not determine cache directory, neither XDG_CACHE_HOME nor HOME are set")
+        }
+    }
+}
+
+/// Hashes all parts of a cache key into a single file name. Parts are length prefixed so
+/// that moving text between them can never produce the same key.
+pub fn key<I, S>(parts: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut hasher = Sha256::new();
+
+    for part in parts {
+        let part = part.as_ref();
+        hasher.update(part.len().to_le_bytes());
+        hasher.update(part.as_bytes());
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+fn entry_path(namespace: &str, key: &str) -> Result<PathBuf> {
+    Ok(cache_dir()?.join(namespace).join(key))
+}
+
+pub fn read(namespace: &str, key: &str) -> Option<String> {
+    let path = entry_path(namespace, key).ok()?;
+
+    match fs::read_to_string(&path) {
+        Ok(value) => {
+            trace!("Cache hit for {namespace}/{key}");
+            Some(value)
+        }
+        Err(_) => {
+            trace!("Cache miss for {namespace}/{key}");
+            None
+        }
+    }
+}
+
+pub fn write(namespace: &str, key: &str, value: &str) -> Result<()> {
+    let path = entry_path(namespace, key)?;
+    let Some(dir) = path.parent() else {
+        bail!("Invalid cache entry path {path:?}");
+    };
+    fs::create_dir_all(dir)?;
+
+    // Write to a temporary file first so concurrent readers never see a partial entry.
+    let tmp = dir.join(format!(".{key}.{}", std::process::id()));
+    fs::write(&tmp, value)?;
+    fs::rename(&tmp, &path)?;
+
+    debug!("Cached {namespace}/{key}");
+    Ok(())
+}
+
+fn count_entries(dir: &Path) -> usize {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return 0;
+    };
+
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(t) if t.is_dir() => count_entries(&entry.path()),
+            Ok(_) => 1,
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Removes every cached entry, returning the cache directory and how many entries were removed.
+pub fn clear() -> Result<(PathBuf, usize)> {
+    let dir = cache_dir()?;
+
+    if !dir.exists() {
+        return Ok((dir, 0));
+    }
+
+    let removed = count_entries(&dir);
+    fs::remove_dir_all(&dir)?;
+
+    Ok((dir, removed))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::{Mutex, MutexGuard};
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    /// Held by tests that change `XDG_CACHE_HOME`, so they never share a cache directory.
+    static CACHE_HOME: Mutex<()> = Mutex::new(());
+
+    /// Points the cache at a new temporary directory for as long as the guard is held.
+    pub(crate) fn temp_cache() -> (MutexGuard<'static, ()>, TempDir) {
+        let guard = CACHE_HOME.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = tempfile::tempdir().unwrap();
+        // SAFETY: every test that touches the environment holds `CACHE_HOME`.
+        unsafe { std::env::set_var("XDG_CACHE_HOME", dir.path()) };
+
+        (guard, dir)
+    }
+
+    #[test]
+    fn key_is_stable() {
+        assert_eq!(
+            key(["eval", "json"]),
+            "97b9f0777028d4f3c73624dbfcaacdc8d16d7ed23a5df539042b233298223e98"
+        );
+        assert_eq!(key(["a", "b"]), key(vec!["a".to_string(), "b".to_string()]));
+    }
+
+    #[test]
+    fn key_separates_parts() {
+        assert_ne!(key(["ab", "c"]), key(["a", "bc"]));
+        assert_ne!(key(["abc"]), key(["abc", ""]));
+        assert_ne!(key(["a", "b"]), key(["b", "a"]));
+    }
+
+    #[test]
+    fn write_and_read() {
+        let (_guard, dir) = temp_cache();
+        assert_eq!(cache_dir().unwrap(), dir.path().join("nilla"));
+
+        assert_eq!(read("eval", "abc"), None);
+        write("eval", "abc", "first").unwrap();
+        assert_eq!(read("eval", "abc").as_deref(), Some("first"));
+        write("eval", "abc", "second").unwrap();
+        assert_eq!(read("eval", "abc").as_deref(), Some("second"));
+
+        // Entries are namespaced and no temporary files are left behind.
+        assert_eq!(read("source", "abc"), None);
+        let files: Vec<_> = fs::read_dir(dir.path().join("nilla").join("eval"))
+            .unwrap()
+            .flatten()
+            .map(|e| e.file_name())
+            .collect();
+        assert_eq!(files, ["abc"]);
+    }
+
+    #[test]
+    fn clear_entries() {
+        let (_guard, dir) = temp_cache();
+        assert_eq!(clear().unwrap().1, 0);
+
+        write("eval", "a", "1").unwrap();
+        write("eval", "b", "2").unwrap();
+        write("source", "c", "3").unwrap();
+
+        let (cleared, removed) = clear().unwrap();
+        assert_eq!(cleared, dir.path().join("nilla"));
+        assert_eq!(removed, 3);
+        assert!(!cleared.exists());
+        assert_eq!(read("eval", "a"), None);
+    }
+}
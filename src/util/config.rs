@@ -0,0 +1,542 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use clap::{CommandFactory, FromArgMatches};
+use nilla_cli_def::Cli;
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+
+use crate::util::search::search_up_for_file;
+
+pub const SYSTEM_CONFIG: &str = "/etc/nilla/config.toml";
+pub const LOCAL_CONFIG: &str = ".nilla.toml";
+
+/// Gets the directory Nilla reads user configuration from, `$XDG_CONFIG_HOME/nilla` or
+/// `~/.config/nilla` when that is not set.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME").filter(|d| !d.is_empty()) {
+        return Ok(PathBuf::from(dir).join("nilla"));
+    }
+
+    match std::env::var_os("HOME").filter(|d| !d.is_empty()) {
+        Some(home) => Ok(PathBuf::from(home).join(".config").join("nilla")),
+        None => {
+            bail!("Could not determine config directory, neither XDG_CONFIG_HOME nor HOME are set")
+        }
+    }
+}
+
+pub fn user_config() -> Result<PathBuf> {
+    Ok(config_dir()?.join("config.toml"))
+}
+
+/// Finds the closest `.nilla.toml`, starting at the current directory. This is not relative to
+/// `--project`, since the project itself can be set in the config.
+pub fn local_config() -> Option<PathBuf> {
+    search_up_for_file(std::env::current_dir().ok()?, LOCAL_CONFIG)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Kind {
+    String,
+    Bool,
+    Integer,
+    Choice(&'static [&'static str]),
+}
+
+/// A configurable option. Options with arguments set the default value of those arguments,
+/// either on the top level command or on each of the listed subcommands.
+#[derive(Debug)]
+pub struct Key {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub description: &'static str,
+    pub subcommands: &'static [&'static str],
+    pub arg: Option<&'static str>,
+}
+
+pub const KEYS: &[Key] = &[
+    Key {
+        name: "project",
+        kind: Kind::String,
+        description: "The nilla project to use",
+        subcommands: &[],
+        arg: Some("project"),
+    },
+    Key {
+        name: "verbose",
+        kind: Kind::Integer,
+        description: "The verbosity level to use",
+        subcommands: &[],
+        arg: Some("verbose"),
+    },
+    Key {
+        name: "quiet",
+        kind: Kind::Bool,
+        description: "Only log errors",
+        subcommands: &[],
+        arg: Some("quiet"),
+    },
+    Key {
+        name: "show-eval-commands",
+        kind: Kind::Bool,
+        description: "Log any ran eval commands",
+        subcommands: &[],
+        arg: Some("show_eval_commands"),
+    },
+    Key {
+        name: "output",
+        kind: Kind::Choice(&["human", "json"]),
+        description: "The format to print command results in",
+        subcommands: &[],
+        arg: Some("output"),
+    },
+    Key {
+        name: "no-eval-cache",
+        kind: Kind::Bool,
+        description: "Always re-evaluate instead of using cached evaluation results",
+        subcommands: &[],
+        arg: Some("no_eval_cache"),
+    },
+    Key {
+        name: "lock-file",
+        kind: Kind::String,
+        description: "The lock file to pin remote projects with",
+        subcommands: &[],
+        arg: Some("lock_file"),
+    },
+    Key {
+        name: "include-untracked",
+        kind: Kind::Bool,
+        description: "Include untracked files that are not ignored when using local git projects",
+        subcommands: &[],
+        arg: Some("include_untracked"),
+    },
+    Key {
+        name: "include-submodules",
+        kind: Kind::Bool,
+        description: "Include checked out submodules when using local git projects",
+        subcommands: &[],
+        arg: Some("include_submodules"),
+    },
+    Key {
+        name: "require-clean",
+        kind: Kind::Bool,
+        description: "Fail when a local git project has uncommitted changes",
+        subcommands: &[],
+        arg: Some("require_clean"),
+    },
+    Key {
+        name: "system",
+        kind: Kind::String,
+        description: "The system to use instead of the current one",
+        subcommands: &["build", "check", "repl", "run", "shell"],
+        arg: Some("system"),
+    },
+    Key {
+        name: "fetch",
+        kind: Kind::Choice(&["git", "tarball"]),
+        description: "How forge sources pinned to a rev are fetched",
+        subcommands: &[],
+        arg: None,
+    },
+    Key {
+        name: "template",
+        kind: Kind::String,
+        description: "The template used by init and new",
+        subcommands: &["init", "new"],
+        arg: Some("template"),
+    },
+    Key {
+        name: "build.no-link",
+        kind: Kind::Bool,
+        description: "Do not link build outputs to the current directory",
+        subcommands: &["build"],
+        arg: Some("no_link"),
+    },
+    Key {
+        name: "check.build",
+        kind: Kind::Bool,
+        description: "Build every package and shell when checking",
+        subcommands: &["check"],
+        arg: Some("build"),
+    },
+    Key {
+        name: "shell.command",
+        kind: Kind::String,
+        description: "The command to run in shells instead of $SHELL",
+        subcommands: &["shell"],
+        arg: Some("command"),
+    },
+];
+
+pub fn get_key(name: &str) -> Result<&'static Key> {
+    match KEYS.iter().find(|k| k.name == name) {
+        Some(key) => Ok(key),
+        None => bail!("Unknown config key {name}, run `nilla config list` to see all keys"),
+    }
+}
+
+impl Key {
+    /// The environment variable overriding this key, eg. `NILLA_BUILD_NO_LINK`.
+    pub fn env_var(&self) -> String {
+        format!(
+            "NILLA_{}",
+            self.name.replace(['.', '-'], "_").to_uppercase()
+        )
+    }
+
+    /// Checks a value for this key, returning it in the form clap expects.
+    pub fn parse(&self, value: &str) -> Result<String> {
+        let valid = match self.kind {
+            Kind::String => true,
+            Kind::Bool => ["true", "false"].contains(&value),
+            Kind::Integer => value.parse::<u8>().is_ok(),
+            Kind::Choice(choices) => choices.contains(&value),
+        };
+
+        if !valid {
+            bail!(
+                "Invalid value {value:?} for {}, expected {}",
+                self.name,
+                self.kind
+            );
+        }
+
+        Ok(value.to_string())
+    }
+
+    /// The default of the argument this key sets, when not configured.
+    pub fn default_value(&self) -> Option<String> {
+        let arg = self.arg?;
+        let command = Cli::command();
+        let command = match self.subcommands.first() {
+            Some(name) => command.find_subcommand(name)?.clone(),
+            None => command,
+        };
+
+        command
+            .get_arguments()
+            .find(|a| a.get_id() == arg)?
+            .get_default_values()
+            .first()
+            .map(|v| v.to_string_lossy().to_string())
+    }
+
+    fn parse_toml(&self, value: &toml::Value) -> Result<String> {
+        match (self.kind, value) {
+            (Kind::Bool, toml::Value::Boolean(b)) => Ok(b.to_string()),
+            (Kind::Integer, toml::Value::Integer(i)) => self.parse(&i.to_string()),
+            (Kind::String | Kind::Choice(_), toml::Value::String(s)) => self.parse(s),
+            _ => bail!(
+                "Invalid value {value} for {}, expected {}",
+                self.name,
+                self.kind
+            ),
+        }
+    }
+
+    fn to_toml(&self, value: &str) -> Result<toml::Value> {
+        let value = self.parse(value)?;
+
+        Ok(match self.kind {
+            Kind::Bool => toml::Value::Boolean(value == "true"),
+            Kind::Integer => toml::Value::Integer(value.parse()?),
+            Kind::String | Kind::Choice(_) => toml::Value::String(value),
+        })
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::String => write!(f, "a string"),
+            Kind::Bool => write!(f, "true or false"),
+            Kind::Integer => write!(f, "a number"),
+            Kind::Choice(choices) => write!(f, "one of: {}", choices.join(", ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    System,
+    User,
+    Local,
+    Env,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::System => write!(f, "system"),
+            Origin::User => write!(f, "user"),
+            Origin::Local => write!(f, "local"),
+            Origin::Env => write!(f, "env"),
+        }
+    }
+}
+
+fn flatten(prefix: &str, table: &toml::Table, out: &mut BTreeMap<String, toml::Value>) {
+    for (name, value) in table {
+        let name = match prefix {
+            "" => name.clone(),
+            _ => format!("{prefix}.{name}"),
+        };
+
+        match value {
+            toml::Value::Table(table) => flatten(&name, table, out),
+            value => {
+                out.insert(name, value.clone());
+            }
+        }
+    }
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    if !path.exists() {
+        return Ok(toml::Table::new());
+    }
+
+    match fs::read_to_string(path)?.parse::<toml::Table>() {
+        Ok(table) => Ok(table),
+        Err(e) => bail!("Could not parse config {}: {e}", path.display()),
+    }
+}
+
+/// The effective configuration, merged from all config files and the environment. Later
+/// layers take precedence: system, user, local and then environment variables.
+#[derive(Debug, Default)]
+pub struct Config {
+    values: BTreeMap<&'static str, (String, Origin)>,
+    /// Problems with files, keys or values that were skipped while loading.
+    warnings: Vec<String>,
+}
+
+impl Config {
+    /// Loads every layer, skipping anything invalid so that a broken config can still be fixed
+    /// with `nilla config`.
+    pub fn load() -> Result<Self> {
+        let mut config = Self::default();
+
+        let mut files = vec![(PathBuf::from(SYSTEM_CONFIG), Origin::System)];
+        match user_config() {
+            Ok(path) => files.push((path, Origin::User)),
+            Err(e) => config
+                .warnings
+                .push(format!("{e}, skipping the user config")),
+        }
+        files.extend(local_config().map(|path| (path, Origin::Local)));
+
+        for (path, origin) in files {
+            let table = match read_table(&path) {
+                Ok(table) => table,
+                Err(e) => {
+                    config.warnings.push(format!("{e}, skipping it"));
+                    continue;
+                }
+            };
+
+            let mut values = BTreeMap::new();
+            flatten("", &table, &mut values);
+
+            for (name, value) in values {
+                let parsed = get_key(&name).and_then(|key| Ok((key, key.parse_toml(&value)?)));
+                match parsed {
+                    Ok((key, value)) => {
+                        config.values.insert(key.name, (value, origin));
+                    }
+                    Err(e) => config.warnings.push(format!("{e} (in {})", path.display())),
+                }
+            }
+        }
+
+        for key in KEYS {
+            if let Ok(value) = std::env::var(key.env_var()) {
+                match key.parse(&value) {
+                    Ok(value) => {
+                        config.values.insert(key.name, (value, Origin::Env));
+                    }
+                    Err(e) => config.warnings.push(format!("{e} (in ${})", key.env_var())),
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn get(&self, name: &str) -> Option<(&str, Origin)> {
+        self.values
+            .get(name)
+            .map(|(value, origin)| (value.as_str(), *origin))
+    }
+
+    /// Makes configured values the defaults of their command line arguments.
+    fn apply(&self, mut command: clap::Command) -> clap::Command {
+        for key in KEYS {
+            let (Some(arg), Some((value, _))) = (key.arg, self.values.get(key.name)) else {
+                continue;
+            };
+
+            if key.subcommands.is_empty() {
+                command = command.mut_arg(arg, |a| a.default_value(value.clone()));
+            } else {
+                for name in key.subcommands {
+                    command = command.mut_subcommand(name, |c| {
+                        c.mut_arg(arg, |a| a.default_value(value.clone()))
+                    });
+                }
+            }
+        }
+
+        command
+    }
+
+    /// Sets a key in a config file, keeping anything else in it.
+    pub fn set(path: &Path, name: &str, value: &str) -> Result<()> {
+        let key = get_key(name)?;
+        let value = key.to_toml(value)?;
+
+        let mut root = read_table(path)?;
+        let mut table = &mut root;
+        let mut segments: Vec<&str> = key.name.split('.').collect();
+        let last = segments.pop().unwrap_or(key.name);
+
+        for segment in segments {
+            let entry = table
+                .entry(segment)
+                .or_insert_with(|| toml::Value::Table(toml::Table::new()));
+            let toml::Value::Table(inner) = entry else {
+                bail!("{segment} is not a table in {}", path.display());
+            };
+            table = inner;
+        }
+        table.insert(last.to_string(), value);
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, toml::to_string_pretty(&root)?)?;
+
+        Ok(())
+    }
+}
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+static CLI: OnceCell<Cli> = OnceCell::new();
+
+pub fn config() -> Result<&'static Config> {
+    CONFIG.get_or_try_init(Config::load)
+}
+
+/// Gets the value of a key that has no command line argument.
+pub fn get(name: &str) -> Option<&'static str> {
+    config().ok()?.get(name).map(|(value, _)| value)
+}
+
+/// Parses the command line with configured values as defaults.
+pub fn load_cli() -> Result<&'static Cli> {
+    CLI.get_or_try_init(|| {
+        let matches = config()?.apply(Cli::command()).get_matches();
+        Ok(Cli::from_arg_matches(&matches)?)
+    })
+}
+
+/// The parsed command line, for code that is not handed the `Cli` by its command.
+pub fn cli() -> &'static Cli {
+    match load_cli() {
+        Ok(cli) => cli,
+        Err(_) => CLI.get_or_init(<Cli as clap::Parser>::parse),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unknown_key() {
+        assert!(get_key("no-such-key").is_err());
+        assert!(get_key("build").is_err());
+    }
+
+    #[test]
+    fn parse_bool() {
+        let key = get_key("quiet").unwrap();
+        assert_eq!(key.parse("true").unwrap(), "true");
+        assert_eq!(key.parse("false").unwrap(), "false");
+        assert!(key.parse("yes").is_err());
+        assert!(key.parse("").is_err());
+    }
+
+    #[test]
+    fn parse_integer() {
+        let key = get_key("verbose").unwrap();
+        assert_eq!(key.parse("3").unwrap(), "3");
+        assert!(key.parse("-1").is_err());
+        assert!(key.parse("300").is_err());
+        assert!(key.parse("three").is_err());
+    }
+
+    #[test]
+    fn parse_choice() {
+        let key = get_key("output").unwrap();
+        assert_eq!(key.parse("json").unwrap(), "json");
+        assert!(key.parse("xml").is_err());
+        assert!(key.parse("JSON").is_err());
+    }
+
+    #[test]
+    fn parse_string() {
+        let key = get_key("build.no-link").unwrap();
+        assert!(key.parse("1").is_err());
+
+        let key = get_key("project").unwrap();
+        assert_eq!(key.parse("github:foo/bar").unwrap(), "github:foo/bar");
+    }
+
+    #[test]
+    fn parse_toml_values() {
+        let key = get_key("quiet").unwrap();
+        assert_eq!(key.parse_toml(&toml::Value::Boolean(true)).unwrap(), "true");
+        assert!(key.parse_toml(&"true".into()).is_err());
+
+        let key = get_key("verbose").unwrap();
+        assert_eq!(key.parse_toml(&toml::Value::Integer(2)).unwrap(), "2");
+        assert!(key.parse_toml(&toml::Value::Integer(-2)).is_err());
+
+        let key = get_key("fetch").unwrap();
+        assert_eq!(key.parse_toml(&"tarball".into()).unwrap(), "tarball");
+        assert!(key.parse_toml(&"zip".into()).is_err());
+    }
+
+    #[test]
+    fn env_vars() {
+        assert_eq!(get_key("quiet").unwrap().env_var(), "NILLA_QUIET");
+        assert_eq!(
+            get_key("build.no-link").unwrap().env_var(),
+            "NILLA_BUILD_NO_LINK"
+        );
+    }
+
+    #[test]
+    fn flatten_tables() {
+        let table: toml::Table = "quiet = true\n[build]\nno-link = true\n".parse().unwrap();
+        let mut values = BTreeMap::new();
+        flatten("", &table, &mut values);
+
+        assert_eq!(
+            values.keys().collect::<Vec<_>>(),
+            ["build.no-link", "quiet"]
+        );
+    }
+}
@@ -1,22 +1,136 @@
+use std::{collections::BTreeSet, fmt};
+
 use once_cell::sync::Lazy;
 use regex::Regex;
 
+/// Exit codes that let scripts tell apart where `nilla` failed.
+pub const EXIT_FAILURE: i32 = 1;
+pub const EXIT_RESOLUTION: i32 = 3;
+pub const EXIT_EVALUATION: i32 = 4;
+pub const EXIT_BUILD: i32 = 5;
+
+#[derive(Debug)]
 pub enum NixError {
     HashMismatch {
         current: String,
         expected: String,
         source: String,
     },
+    Other {
+        message: String,
+    },
+}
+
+impl fmt::Display for NixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NixError::HashMismatch {
+                current,
+                expected,
+                source,
+            } => write!(
+                f,
+                "Hash mismatch for {source}, specified {current} but got {expected}"
+            ),
+            NixError::Other { message } => write!(f, "{message}"),
+        }
+    }
+}
+
+/// A project that could not be resolved to a source in the store.
+#[derive(Debug)]
+pub struct ResolutionError {
+    pub uri: String,
+    pub error: anyhow::Error,
+}
+
+impl fmt::Display for ResolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Could not find project {}: {}", self.uri, self.error)
+    }
+}
+
+impl std::error::Error for ResolutionError {}
+
+/// A failed `nix eval`.
+#[derive(Debug)]
+pub struct EvalError {
+    pub stderr: String,
 }
 
-fn get_capture(regex: &Lazy<Regex>, input: &str, i: usize) -> String {
-    regex
-        .captures(input)
-        .unwrap()
-        .get(i)
-        .unwrap()
-        .as_str()
-        .to_string()
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "nix eval failed\n{}", self.stderr)
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// A failed `nix build`. Its errors have already been shown while building, so only the ones
+/// that were recognized are repeated.
+#[derive(Debug)]
+pub struct BuildError {
+    pub code: Option<i32>,
+    pub stderr: String,
+    pub errors: Vec<NixError>,
+}
+
+impl BuildError {
+    pub fn new(code: Option<i32>, stderr: String) -> Self {
+        Self {
+            code,
+            errors: handle_error(&stderr),
+            stderr,
+        }
+    }
+
+    /// The derivations that Nix reported as failed, including the ones whose dependencies failed.
+    pub fn failed_derivations(&self) -> BTreeSet<&str> {
+        static DERIVATION: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r"'(/nix/store/[^']+\.drv)'").unwrap());
+
+        DERIVATION
+            .captures_iter(&self.stderr)
+            .filter_map(|c| c.get(1))
+            .map(|m| m.as_str())
+            .collect()
+    }
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "nix build failed with exit code {code}")?,
+            None => write!(f, "nix build was terminated by a signal")?,
+        }
+        for error in &self.errors {
+            write!(f, "\n{error}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Picks the exit code for an error based on the first typed failure in its chain.
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    for cause in error.chain() {
+        if cause.is::<ResolutionError>() {
+            return EXIT_RESOLUTION;
+        }
+        if cause.is::<EvalError>() {
+            return EXIT_EVALUATION;
+        }
+        if cause.is::<BuildError>() {
+            return EXIT_BUILD;
+        }
+    }
+
+    EXIT_FAILURE
+}
+
+fn get_capture(regex: &Lazy<Regex>, input: &str, i: usize) -> Option<String> {
+    Some(regex.captures(input)?.get(i)?.as_str().to_string())
 }
 
 pub fn handle_error(stderr: &str) -> Vec<NixError> {
@@ -29,21 +143,23 @@ pub fn handle_error(stderr: &str) -> Vec<NixError> {
         Regex::new(r"error: hash mismatch in fixed-output derivation '([^']+)':").unwrap()
     });
 
+    let hash_mismatch = |source: &Lazy<Regex>| {
+        Some(NixError::HashMismatch {
+            current: get_capture(&CURRENT_HASH, stderr, 1)?,
+            expected: get_capture(&EXPECTED_HASH, stderr, 1)?,
+            source: get_capture(source, stderr, 1)?,
+        })
+    };
+
     let mut errors = vec![];
 
-    if HASH_MISMATCH.is_match(stderr) {
-        errors.push(NixError::HashMismatch {
-            current: get_capture(&CURRENT_HASH, stderr, 0),
-            expected: get_capture(&EXPECTED_HASH, stderr, 0),
-            source: get_capture(&HASH_MISMATCH, stderr, 0),
-        });
-    }
-    if FIXED_HASH_MISMATCH.is_match(stderr) {
-        errors.push(NixError::HashMismatch {
-            current: get_capture(&CURRENT_HASH, stderr, 0),
-            expected: get_capture(&EXPECTED_HASH, stderr, 0),
-            source: get_capture(&FIXED_HASH_MISMATCH, stderr, 0),
-        });
+    for source in [&HASH_MISMATCH, &FIXED_HASH_MISMATCH] {
+        if source.is_match(stderr) {
+            // Messages in a format we do not know are passed on as they are.
+            errors.push(hash_mismatch(source).unwrap_or_else(|| NixError::Other {
+                message: stderr.trim().to_string(),
+            }));
+        }
     }
 
     errors
@@ -1,7 +1,181 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
+use log::{debug, trace};
+use serde::Serialize;
 use tokio::process::Command;
 
+/// How a working tree is attached to its git directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// A regular checkout with a `.git` directory.
+    Repository,
+    /// A linked worktree, including checkouts of a bare repository.
+    Worktree,
+    /// A submodule checked out inside another repository.
+    Submodule,
+    /// A bare repository, which has no working tree to take files from.
+    Bare,
+}
+
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Layout::Repository => write!(f, "repository"),
+            Layout::Worktree => write!(f, "worktree"),
+            Layout::Submodule => write!(f, "submodule"),
+            Layout::Bare => write!(f, "bare repository"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Repository {
+    /// The top level of the working tree, or the git directory of a bare repository.
+    pub root: PathBuf,
+    pub layout: Layout,
+}
+
+/// The state of a local repository's working tree at the time it was resolved.
+#[derive(Debug, Clone, Serialize)]
+pub struct GitStatus {
+    pub layout: Layout,
+    /// The commit HEAD points at, or `None` if there are no commits yet.
+    pub rev: Option<String>,
+    /// The checked out branch, or `None` if HEAD is detached.
+    pub branch: Option<String>,
+    pub dirty: bool,
+    /// Tracked files with uncommitted changes, relative to the repository.
+    pub modified: Vec<PathBuf>,
+}
+
+async fn run_git(repo: &Path, args: &[&str]) -> anyhow::Result<Option<String>> {
+    let output = Command::new("git")
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8(output.stdout)?))
+}
+
+/// Finds the working tree containing `dir`, whether its `.git` is a directory or a file pointing
+/// elsewhere, or the bare repository containing it. Returns `None` outside of a repository.
+pub(crate) async fn find_repository<P>(dir: P) -> anyhow::Result<Option<Repository>>
+where
+    P: Into<PathBuf>,
+{
+    let dir: PathBuf = dir.into();
+
+    match run_git(&dir, &["rev-parse", "--is-inside-work-tree"]).await? {
+        Some(inside) if inside.trim() == "true" => {}
+        Some(_) => return find_bare_repository(&dir).await,
+        None => return Ok(None),
+    }
+
+    let Some(output) = run_git(
+        &dir,
+        &[
+            "rev-parse",
+            "--path-format=absolute",
+            "--show-toplevel",
+            "--git-dir",
+            "--git-common-dir",
+            "--show-superproject-working-tree",
+        ],
+    )
+    .await?
+    else {
+        anyhow::bail!("git rev-parse failed in {dir:?}");
+    };
+
+    let mut lines = output.lines();
+    let (Some(root), Some(git_dir), Some(common_dir)) = (lines.next(), lines.next(), lines.next())
+    else {
+        anyhow::bail!("Unexpected output from git rev-parse in {dir:?}: {output}");
+    };
+
+    // The superproject is only printed for submodules.
+    let layout = if lines.next().is_some_and(|l| !l.is_empty()) {
+        Layout::Submodule
+    } else if Path::new(git_dir) != Path::new(common_dir) {
+        Layout::Worktree
+    } else {
+        Layout::Repository
+    };
+
+    trace!("Found git {layout} at {root} with git directory {git_dir}");
+
+    Ok(Some(Repository {
+        root: PathBuf::from(root),
+        layout,
+    }))
+}
+
+async fn find_bare_repository(dir: &Path) -> anyhow::Result<Option<Repository>> {
+    let bare = run_git(dir, &["rev-parse", "--is-bare-repository"]).await?;
+    if bare.is_none_or(|b| b.trim() != "true") {
+        debug!("{dir:?} is inside a git directory without a working tree");
+        return Ok(None);
+    }
+
+    let Some(git_dir) = run_git(dir, &["rev-parse", "--absolute-git-dir"]).await? else {
+        anyhow::bail!("git rev-parse failed in {dir:?}");
+    };
+
+    trace!("Found bare git repository at {}", git_dir.trim());
+
+    Ok(Some(Repository {
+        root: PathBuf::from(git_dir.trim()),
+        layout: Layout::Bare,
+    }))
+}
+
+pub(crate) async fn get_status(repository: &Repository) -> anyhow::Result<GitStatus> {
+    let repo = &repository.root;
+
+    let rev = run_git(repo, &["rev-parse", "--verify", "-q", "HEAD"])
+        .await?
+        .map(|r| r.trim().to_string());
+    let branch = run_git(repo, &["symbolic-ref", "-q", "--short", "HEAD"])
+        .await?
+        .map(|b| b.trim().to_string());
+
+    let Some(status) = run_git(
+        repo,
+        &["status", "--porcelain=v1", "-z", "--untracked-files=no"],
+    )
+    .await?
+    else {
+        anyhow::bail!("git status failed in {repo:?}");
+    };
+
+    // Each entry is `XY path`, renames and copies are followed by their original path.
+    let mut modified = vec![];
+    let mut entries = status.split('\0').filter(|e| !e.is_empty());
+    while let Some(entry) = entries.next() {
+        let Some(path) = entry.get(3..) else {
+            continue;
+        };
+        if entry.starts_with(['R', 'C']) {
+            entries.next();
+        }
+        modified.push(PathBuf::from(path));
+    }
+
+    Ok(GitStatus {
+        layout: repository.layout,
+        rev,
+        branch,
+        dirty: !modified.is_empty(),
+        modified,
+    })
+}
+
 pub(crate) async fn get_untracked_files<P>(repo: P) -> anyhow::Result<Vec<PathBuf>>
 where
     P: Into<PathBuf>,
@@ -18,3 +192,50 @@ where
 
     Ok(output.trim().lines().map(PathBuf::from).collect())
 }
+
+async fn ls_files(repo: &Path, args: &[&str]) -> anyhow::Result<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .arg("ls-files")
+        .arg("-z")
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!("git ls-files failed:\n{stderr}");
+    }
+
+    let output = String::from_utf8(output.stdout)?;
+
+    Ok(output
+        .split('\0')
+        .filter(|f| !f.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Lists every tracked file, relative to the repository. Untracked files that are not ignored and
+/// the files of checked out submodules can be included as well.
+pub(crate) async fn get_worktree_files<P>(
+    repo: P,
+    untracked: bool,
+    submodules: bool,
+) -> anyhow::Result<Vec<PathBuf>>
+where
+    P: Into<PathBuf>,
+{
+    let repo: PathBuf = repo.into();
+
+    // Git does not allow listing untracked files and recursing into submodules at once.
+    let mut files = match submodules {
+        true => ls_files(&repo, &["--cached", "--recurse-submodules"]).await?,
+        false => ls_files(&repo, &["--cached"]).await?,
+    };
+    if untracked {
+        files.extend(ls_files(&repo, &["--others", "--exclude-standard"]).await?);
+    }
+
+    Ok(files)
+}
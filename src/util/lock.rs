@@ -0,0 +1,121 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+use crate::util::config;
+
+pub const LOCK_FILE: &str = "nilla.lock";
+const LOCK_VERSION: u32 = 1;
+
+/// The resolved revision and NAR hash of a remote project source.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedSource {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rev: Option<String>,
+    pub hash: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct LockFile {
+    version: u32,
+    sources: BTreeMap<String, LockedSource>,
+}
+
+impl Default for LockFile {
+    fn default() -> Self {
+        Self {
+            version: LOCK_VERSION,
+            sources: BTreeMap::new(),
+        }
+    }
+}
+
+/// A lock file pinning remote project sources, keyed by the project URI they were resolved from.
+#[derive(Debug)]
+pub struct Lock {
+    path: PathBuf,
+    file: LockFile,
+    update: bool,
+}
+
+impl Lock {
+    fn read(path: &Path) -> Result<LockFile> {
+        let contents = fs::read_to_string(path)?;
+        let file: LockFile = match serde_json::from_str(&contents) {
+            Ok(f) => f,
+            Err(e) => bail!("Could not parse lock file {}: {e}", path.display()),
+        };
+
+        if file.version != LOCK_VERSION {
+            bail!(
+                "Unsupported lock file version {} in {}",
+                file.version,
+                path.display()
+            );
+        }
+
+        Ok(file)
+    }
+
+    /// Loads the lock file selected on the command line. Without `--lock-file`, `nilla.lock` in
+    /// the current directory is used if it exists, or created when `--update-lock` is passed.
+    pub fn from_cli() -> Result<Option<Self>> {
+        let cli = config::cli();
+
+        let path = match &cli.lock_file {
+            Some(path) => path.clone(),
+            None => {
+                let path = std::env::current_dir()?.join(LOCK_FILE);
+                if !path.exists() && !cli.update_lock {
+                    return Ok(None);
+                }
+                path
+            }
+        };
+
+        let file = if path.exists() {
+            Self::read(&path)?
+        } else {
+            LockFile::default()
+        };
+
+        debug!("Using lock file {}", path.display());
+
+        Ok(Some(Self {
+            path,
+            file,
+            update: cli.update_lock,
+        }))
+    }
+
+    /// Gets the locked source for a URI, unless the lock is being updated.
+    pub fn get(&self, uri: &str) -> Option<&LockedSource> {
+        if self.update {
+            return None;
+        }
+
+        self.file.sources.get(uri)
+    }
+
+    /// Records the resolution of a URI, writing the lock file if anything changed.
+    pub fn record(&mut self, uri: &str, source: LockedSource) -> Result<()> {
+        if self.file.sources.get(uri) == Some(&source) {
+            return Ok(());
+        }
+
+        self.file.sources.insert(uri.to_string(), source);
+        fs::write(
+            &self.path,
+            format!("{}\n", serde_json::to_string_pretty(&self.file)?),
+        )?;
+
+        info!("Updated {uri} in {}", self.path.display());
+        Ok(())
+    }
+}
@@ -1,5 +1,12 @@
+pub mod cache;
+pub mod config;
 pub mod errors;
 pub mod git;
+pub mod lock;
 pub mod nix;
+pub mod npins;
+pub mod output;
 pub mod project;
+pub mod registry;
 pub mod search;
+pub mod templates;
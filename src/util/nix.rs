@@ -1,18 +1,30 @@
+pub mod expr;
+pub mod progress;
+
 use std::{
     fs::create_dir_all,
     path::{Path, PathBuf},
     process::Stdio,
 };
 
-use anyhow::{Result, anyhow, bail};
-use log::{debug, error, info, trace};
-use serde_json::Value;
-use tokio::process::Command;
+use anyhow::{Result, bail};
+use log::{debug, error, info, trace, warn};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+};
 
-use crate::util::project::remove_filename_from_path;
+use crate::util::{
+    cache, config,
+    errors::{BuildError, EvalError},
+    nix::progress::Progress,
+    project::remove_filename_from_path,
+};
 
 pub struct EvalOpts {
     pub json: bool,
+    pub raw: bool,
     pub impure: bool,
 }
 
@@ -20,6 +32,7 @@ impl Default for EvalOpts {
     fn default() -> Self {
         Self {
             json: false,
+            raw: false,
             impure: true,
         }
     }
@@ -31,7 +44,7 @@ pub enum EvalResult {
     Raw(String),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FixedOutputStoreEntry {
     pub path: PathBuf,
     pub hash: String,
@@ -70,17 +83,51 @@ where
     store_name
 }
 
+fn parse_eval_output(stdout: &str, opts: &EvalOpts) -> Result<EvalResult> {
+    if opts.json {
+        Ok(EvalResult::Json(serde_json::from_str(stdout.trim())?))
+    } else {
+        Ok(EvalResult::Raw(stdout.trim().to_string()))
+    }
+}
+
 pub async fn evaluate(code: &str, opts: EvalOpts) -> Result<EvalResult> {
-    if <nilla_cli_def::Cli as clap::Parser>::parse().show_eval_commands {
+    let cli = config::cli();
+    if cli.show_eval_commands {
         info!("{code}");
     }
 
+    evaluate_cached(code, opts, !cli.no_eval_cache).await
+}
+
+/// Evaluates `code`, reusing and storing the result of pure evaluations when `use_cache` is set.
+async fn evaluate_cached(code: &str, opts: EvalOpts, use_cache: bool) -> Result<EvalResult> {
+    // Pure evaluations can only reach content-addressed paths, and project expressions embed
+    // the source hash, subpath and system, so the expression text is a complete cache key.
+    let cache_key = (!opts.impure && use_cache).then(|| {
+        cache::key([
+            code,
+            if opts.json { "json" } else { "" },
+            if opts.raw { "raw" } else { "" },
+        ])
+    });
+
+    if let Some(key) = &cache_key
+        && let Some(cached) = cache::read("eval", key)
+    {
+        debug!("Using cached evaluation result");
+        return parse_eval_output(&cached, &opts);
+    }
+
     let mut args: Vec<&str> = vec![];
     args.append(&mut vec!["eval", "--show-trace"]);
 
     if opts.json {
         args.push("--json");
     }
+    if opts.raw {
+        args.push("--raw");
+    }
     if opts.impure {
         args.push("--impure");
     }
@@ -91,26 +138,46 @@ pub async fn evaluate(code: &str, opts: EvalOpts) -> Result<EvalResult> {
     let output = Command::new("nix").args(args).output().await?;
 
     if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        bail!("nix eval failed\n{stderr}")
+        return Err(EvalError {
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+        .into());
     }
 
     let stdout = String::from_utf8_lossy(&output.stdout);
+    let result = parse_eval_output(&stdout, &opts)?;
 
-    if opts.json {
-        Ok(EvalResult::Json(serde_json::from_str(stdout.trim())?))
-    } else {
-        Ok(EvalResult::Raw(stdout.trim().to_string()))
+    if let Some(key) = &cache_key
+        && let Err(e) = cache::write("eval", key, &stdout)
+    {
+        warn!("Failed to cache evaluation result: {e}");
     }
+
+    Ok(result)
 }
 
+/// Reads the system from the Nix configuration, which avoids a full evaluation. Older versions of
+/// Nix without `nix config show` fall back to evaluating `builtins.currentSystem`.
 pub async fn get_system() -> Result<String> {
     trace!("Getting system platform");
+    let output = Command::new("nix")
+        .args(["config", "show", "system"])
+        .output()
+        .await?;
+
+    let system = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !system.is_empty() {
+        debug!("Got system {system}");
+        return Ok(system);
+    }
+
+    debug!("Could not read the system from the Nix configuration, evaluating it instead");
     match evaluate(
         "builtins.currentSystem",
         EvalOpts {
             json: true,
             impure: true,
+            raw: false,
         },
     )
     .await?
@@ -267,6 +334,14 @@ pub struct BuildOpts<'a> {
 }
 
 pub async fn build<P>(file: P, name: &str, opts: BuildOpts<'_>) -> Result<Vec<String>>
+where
+    P: AsRef<Path>,
+{
+    build_all(file, &[name], opts).await
+}
+
+/// Builds several attributes with a single `nix build`, continuing past failed builds.
+pub async fn build_all<P>(file: P, names: &[&str], opts: BuildOpts<'_>) -> Result<Vec<String>>
 where
     P: AsRef<Path>,
 {
@@ -277,25 +352,48 @@ where
     if opts.report {
         args.push("--print-out-paths");
     }
+    if names.len() > 1 {
+        args.push("--keep-going");
+    }
     args.push("-f");
     args.push(file.as_ref().to_str().unwrap());
     if !opts.system.is_empty() {
         args.push("--system");
         args.push(opts.system);
     };
-    args.push(name);
+    args.extend(["--log-format", "internal-json"]);
+    args.extend(names);
     debug!("Running nix build:\nnix {}", args.join(" "));
-    let cmd = Command::new("nix")
+    let mut cmd = Command::new("nix")
         .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .args(args)
         .spawn()?;
 
-    Ok(
-        String::from_utf8_lossy(&cmd.wait_with_output().await.unwrap().stdout)
-            .lines()
-            .map(|s| s.to_owned())
-            .collect(),
-    )
+    // Progress is shown as it comes in, while errors are kept to report the failure afterwards.
+    let stderr = cmd.stderr.take();
+    let captured = tokio::spawn(async move {
+        let mut progress = Progress::new();
+        if let Some(stderr) = stderr {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                progress.line(&line);
+            }
+        }
+        progress.finish()
+    });
+
+    let output = cmd.wait_with_output().await?;
+    let stderr = captured.await?;
+
+    if !output.status.success() {
+        return Err(BuildError::new(output.status.code(), stderr).into());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(|s| s.to_owned())
+        .collect())
 }
 
 pub struct ShellOpts<'a> {
@@ -337,100 +435,102 @@ where
     }
 }
 
-pub struct GetMainProgramOpts<'a> {
-    pub system: &'a str,
+pub fn repl(code: &str) {
+    // Passing the expression directly avoids a temporary file that could never be cleaned up,
+    // since this process is replaced by the REPL.
+    debug!("Running nix repl:\nnix repl --expr {code}");
+    debug!("Replacing process with nix repl");
+    if let Err(e) = cargo_util::ProcessBuilder::new("nix")
+        .args(&["repl", "--expr", code])
+        .exec_replace()
+    {
+        error!("Failed to start nix repl: {e}");
+        std::process::exit(1);
+    }
 }
 
-pub async fn get_main_program(
-    file: &str,
-    entry: FixedOutputStoreEntry,
-    name: &str,
-    opts: GetMainProgramOpts<'_>,
-) -> Result<String> {
-    let file_str = entry.path.to_str().unwrap();
-
-    let hash = entry.hash;
-
-    let store_path_name = get_store_path_name(&entry.path);
-
-    let main = evaluate(
-        &format!(
-            "
-			let
-        source = builtins.path {{ path = \"{file_str}\"; sha256 = \"{hash}\"; name = \"{store_path_name}\"; }};
-        project = import \"${{source}}/{file}\";
-				system = \"{}\";
-				name = \"{name}\";
-			in
-				project.packages.${{name}}.result.${{system}}.meta.mainProgram or name
-			",
-            if opts.system.is_empty() {
-                get_system().await?
-            } else {
-                opts.system.to_string()
-            }
-        ),
-        EvalOpts {
-            json: true,
-            impure: false,
-        },
-    )
-    .await?;
+#[cfg(test)]
+mod tests {
+    use std::{fs, os::unix::fs::PermissionsExt, path::Path};
 
-    match main {
-        EvalResult::Json(Value::String(s)) => Ok(s),
-        _ => bail!("Somehow got raw or wrong type"),
-    }
-}
+    use super::*;
+    use crate::util::cache;
 
-pub async fn exists_in_project(
-    file: &str,
-    entry: FixedOutputStoreEntry,
-    name: &str,
-) -> Result<bool> {
-    info!("Checking project for value");
-    let file_str = entry.path.to_str().unwrap();
-
-    let hash = entry.hash;
-
-    let store_path_name = get_store_path_name(&entry.path);
-
-    let code = if name.contains('.') {
-        let parts = name.split('.').collect::<Vec<&str>>();
-        let last = parts.last().ok_or(anyhow!("How did we get here"))?;
-        let init = &parts[0..parts.len() - 1].join(".");
-        format!(
-            "
-            let
-              source = builtins.path {{ path = \"{file_str}\"; sha256 = \"{hash}\"; name = \"{store_path_name}\"; }};
-              project = import \"${{source}}/{file}\";
-            in
-              (project.{init} or {{}}) ? {last}
-            "
+    /// Puts a `nix` on the PATH that prints `"result"` and records every time it is run.
+    fn fake_nix(dir: &Path) {
+        let nix = dir.join("nix");
+        fs::write(
+            &nix,
+            "#!/bin/sh\necho run >> \"$(dirname \"$0\")/runs\"\necho '\"result\"'\n",
         )
-    } else {
-        format!(
-            "
-		let
-      source = builtins.path {{ path = \"{file_str}\"; sha256 = \"{hash}\"; name = \"{store_path_name}\"; }};
-      project = import \"${{source}}/{file}\";
-		in
-			project ? {name}
-		"
+        .unwrap();
+        fs::set_permissions(&nix, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path = std::env::var_os("PATH").unwrap_or_default();
+        let path = std::env::join_paths(
+            std::iter::once(dir.to_path_buf()).chain(std::env::split_paths(&path)),
         )
-    };
+        .unwrap();
+        // SAFETY: the cache guard held by the caller serializes tests that touch the environment.
+        unsafe { std::env::set_var("PATH", path) };
+    }
 
-    let result = evaluate(
-        &code,
-        EvalOpts {
+    fn runs(dir: &Path) -> usize {
+        fs::read_to_string(dir.join("runs"))
+            .map(|runs| runs.lines().count())
+            .unwrap_or_default()
+    }
+
+    fn eval(code: &str, impure: bool, use_cache: bool) -> serde_json::Value {
+        let opts = EvalOpts {
             json: true,
-            impure: false,
-        },
-    )
-    .await?;
+            raw: false,
+            impure,
+        };
+        let result = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(evaluate_cached(code, opts, use_cache))
+            .unwrap();
+
+        match result {
+            EvalResult::Json(value) => value,
+            EvalResult::Raw(raw) => panic!("expected JSON, got {raw}"),
+        }
+    }
 
-    match result {
-        EvalResult::Json(Value::Bool(b)) => Ok(b),
-        _ => bail!("Got a non boolean result {result:?}"),
+    #[test]
+    fn evaluation_cache() {
+        let (_guard, dir) = cache::tests::temp_cache();
+        let path = std::env::var_os("PATH");
+        fake_nix(dir.path());
+
+        // Pure evaluations are stored and then read back without running Nix.
+        assert_eq!(eval("1 + 1", false, true), "result");
+        assert_eq!(runs(dir.path()), 1);
+        assert_eq!(eval("1 + 1", false, true), "result");
+        assert_eq!(runs(dir.path()), 1);
+
+        // Different expressions do not share an entry.
+        eval("2 + 2", false, true);
+        assert_eq!(runs(dir.path()), 2);
+
+        // Impure evaluations are neither read from nor written to the cache.
+        eval("1 + 1", true, true);
+        eval("3 + 3", true, true);
+        eval("3 + 3", true, true);
+        assert_eq!(runs(dir.path()), 5);
+
+        // Without the cache, as with --no-eval-cache, existing entries are ignored.
+        eval("1 + 1", false, false);
+        assert_eq!(runs(dir.path()), 6);
+
+        assert_eq!(cache::clear().unwrap().1, 2);
+        eval("1 + 1", false, true);
+        assert_eq!(runs(dir.path()), 7);
+
+        if let Some(path) = path {
+            // SAFETY: see `fake_nix`.
+            unsafe { std::env::set_var("PATH", path) };
+        }
     }
 }
@@ -0,0 +1,309 @@
+// Helpers for building Nix expressions out of values that come from users, such as attribute
+// names, URLs and paths. Anything spliced into an expression should go through these so that
+// quotes or `${` in the input can never change the meaning of the generated code.
+
+use std::fmt::{self, Display};
+
+use anyhow::bail;
+
+use super::{FixedOutputStoreEntry, get_store_path_name};
+
+const KEYWORDS: &[&str] = &[
+    "assert", "else", "if", "in", "inherit", "let", "or", "rec", "then", "with",
+];
+
+/// Escapes a value so that it can be placed between the quotes of a Nix string.
+pub fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '$' if chars.peek() == Some(&'{') => out.push_str("\\$"),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Quotes a value as a Nix string literal.
+pub fn string(value: &str) -> String {
+    format!("\"{}\"", escape(value))
+}
+
+/// Renders a list of already built expressions.
+pub fn list<I, S>(items: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let items = items
+        .into_iter()
+        .map(|i| i.as_ref().to_string())
+        .collect::<Vec<String>>();
+
+    if items.is_empty() {
+        "[ ]".to_string()
+    } else {
+        format!("[ {} ]", items.join(" "))
+    }
+}
+
+/// Renders a list of Nix strings.
+pub fn string_list<I, S>(items: I) -> String
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    list(items.into_iter().map(|i| string(i.as_ref())))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    let valid_start = match chars.next() {
+        Some(c) => c.is_ascii_alphabetic() || c == '_',
+        None => false,
+    };
+
+    valid_start
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '\'' || c == '-')
+        && !KEYWORDS.contains(&name)
+}
+
+/// Renders an attribute name, quoting it when it is not a plain identifier.
+pub fn attr_name(name: &str) -> String {
+    if is_identifier(name) {
+        name.to_string()
+    } else {
+        string(name)
+    }
+}
+
+/// A path of attribute names such as `packages.default.result`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AttrPath(Vec<String>);
+
+impl AttrPath {
+    pub fn new<I, S>(segments: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self(segments.into_iter().map(|s| s.into()).collect())
+    }
+
+    /// Parses a dotted attribute path as written by a user. Segments may be double quoted to
+    /// include dots, eg: `packages."my.package".result`.
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut segments = vec![];
+        let mut current = String::new();
+        let mut chars = input.chars();
+        let mut quoted = false;
+        let mut was_quoted = false;
+
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    was_quoted = true;
+                }
+                '\\' if quoted => match chars.next() {
+                    Some(escaped) => current.push(escaped),
+                    None => bail!("Unexpected end of attribute path {input}"),
+                },
+                '.' if !quoted => {
+                    if current.is_empty() && !was_quoted {
+                        bail!("Empty attribute name in {input}");
+                    }
+                    segments.push(std::mem::take(&mut current));
+                    was_quoted = false;
+                }
+                _ => current.push(c),
+            }
+        }
+
+        if quoted {
+            bail!("Unterminated quote in attribute path {input}");
+        }
+        if current.is_empty() && !was_quoted {
+            bail!("Empty attribute name in {input}");
+        }
+        segments.push(current);
+
+        Ok(Self(segments))
+    }
+
+    pub fn segments(&self) -> &[String] {
+        &self.0
+    }
+
+    pub fn first(&self) -> Option<&str> {
+        self.0.first().map(|s| s.as_str())
+    }
+
+    pub fn join(&self, other: &AttrPath) -> Self {
+        Self(self.0.iter().chain(other.0.iter()).cloned().collect())
+    }
+
+    /// Renders the path as a list of strings, useful with `builtins.foldl'` style lookups.
+    pub fn to_list(&self) -> String {
+        string_list(&self.0)
+    }
+}
+
+impl Display for AttrPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered = self.0.iter().map(|s| attr_name(s)).collect::<Vec<String>>();
+        write!(f, "{}", rendered.join("."))
+    }
+}
+
+/// Selects `path` from the expression `base`.
+pub fn select(base: &str, path: &AttrPath) -> String {
+    format!("{base}.{path}")
+}
+
+/// Checks whether the expression `base` has the attribute `path`.
+pub fn has_attr(base: &str, path: &AttrPath) -> String {
+    format!("{base} ? {path}")
+}
+
+/// Creates an expression that references a fixed output store entry, making it usable in
+/// pure evaluation.
+pub fn fixed_source(entry: &FixedOutputStoreEntry) -> String {
+    format!(
+        "builtins.path {{ path = {}; sha256 = {}; name = {}; }}",
+        string(entry.path.to_str().unwrap()),
+        string(&entry.hash),
+        string(&get_store_path_name(&entry.path))
+    )
+}
+
+/// A `let ... in ...` expression.
+#[derive(Debug, Clone, Default)]
+pub struct Let {
+    bindings: Vec<(String, String)>,
+}
+
+impl Let {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn bind<S>(mut self, name: &str, value: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.bindings.push((attr_name(name), value.into()));
+        self
+    }
+
+    pub fn body(self, body: &str) -> String {
+        let mut out = String::from("let\n");
+        for (name, value) in self.bindings {
+            out.push_str(&format!("  {name} = {value};\n"));
+        }
+        out.push_str("in\n");
+        out.push_str(body);
+        out
+    }
+}
+
+/// Binds `source` to the store entry and `project` to the imported `file` within it.
+pub fn import_project(entry: &FixedOutputStoreEntry, file: &str) -> Let {
+    Let::new().bind("source", fixed_source(entry)).bind(
+        "project",
+        format!("import \"${{source}}/{}\"", escape(file)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_special_characters() {
+        assert_eq!(escape(r#"say "hi""#), r#"say \"hi\""#);
+        assert_eq!(escape(r"C:\path"), r"C:\\path");
+        assert_eq!(escape("a\nb\tc\r"), r"a\nb\tc\r");
+    }
+
+    #[test]
+    fn escape_interpolation() {
+        assert_eq!(escape("${builtins.abort 1}"), r"\${builtins.abort 1}");
+        assert_eq!(escape("$HOME and $"), "$HOME and $");
+        assert_eq!(escape(r"\${x}"), r"\\\${x}");
+    }
+
+    #[test]
+    fn string_quotes_value() {
+        assert_eq!(string(""), r#""""#);
+        assert_eq!(string(r#"a"${b}"#), r#""a\"\${b}""#);
+        assert_eq!(string_list(["a", "b"]), r#"[ "a" "b" ]"#);
+        assert_eq!(string_list::<_, &str>([]), "[ ]");
+    }
+
+    #[test]
+    fn attr_name_identifiers() {
+        assert_eq!(attr_name("hello"), "hello");
+        assert_eq!(attr_name("x86_64-linux"), "x86_64-linux");
+        assert_eq!(attr_name("_private'"), "_private'");
+    }
+
+    #[test]
+    fn attr_name_quotes_others() {
+        assert_eq!(attr_name("my.package"), r#""my.package""#);
+        assert_eq!(attr_name("in"), r#""in""#);
+        assert_eq!(attr_name("1password"), r#""1password""#);
+        assert_eq!(attr_name(""), r#""""#);
+        assert_eq!(attr_name("${x}"), r#""\${x}""#);
+        assert_eq!(attr_name(r#"a"b"#), r#""a\"b""#);
+    }
+
+    #[test]
+    fn parse_plain_path() {
+        let path = AttrPath::parse("packages.default.result").unwrap();
+        assert_eq!(path, AttrPath::new(["packages", "default", "result"]));
+        assert_eq!(path.first(), Some("packages"));
+        assert_eq!(path.to_string(), "packages.default.result");
+    }
+
+    #[test]
+    fn parse_quoted_segments() {
+        let path = AttrPath::parse(r#"packages."my.package".result"#).unwrap();
+        assert_eq!(path.segments(), ["packages", "my.package", "result"]);
+        assert_eq!(path.to_string(), r#"packages."my.package".result"#);
+
+        let path = AttrPath::parse(r#""a\"b".c"#).unwrap();
+        assert_eq!(path.segments(), [r#"a"b"#, "c"]);
+
+        let path = AttrPath::parse(r#"a."""#).unwrap();
+        assert_eq!(path.segments(), ["a", ""]);
+
+        let path = AttrPath::parse(r#"a."${b}""#).unwrap();
+        assert_eq!(path.to_string(), r#"a."\${b}""#);
+    }
+
+    #[test]
+    fn parse_invalid_paths() {
+        assert!(AttrPath::parse("").is_err());
+        assert!(AttrPath::parse("a..b").is_err());
+        assert!(AttrPath::parse("a.").is_err());
+        assert!(AttrPath::parse(".a").is_err());
+        assert!(AttrPath::parse(r#"a."b"#).is_err());
+        assert!(AttrPath::parse(r#""a\"#).is_err());
+    }
+
+    #[test]
+    fn let_expression() {
+        let code = Let::new().bind("a", "1").bind("in", string("x")).body("a");
+        assert_eq!(code, "let\n  a = 1;\n  \"in\" = \"x\";\nin\na");
+    }
+}
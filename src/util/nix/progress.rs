@@ -0,0 +1,430 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    io::IsTerminal,
+    time::{Duration, Instant},
+};
+
+use colored::Colorize;
+use log::{Level, debug, error, info, trace, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+// Activity and result types from Nix's `src/libutil/logging.hh`.
+const ACTIVITY_COPY_PATHS: u64 = 103;
+const ACTIVITY_BUILDS: u64 = 104;
+const ACTIVITY_BUILD: u64 = 105;
+const ACTIVITY_SUBSTITUTE: u64 = 108;
+
+const RESULT_BUILD_LOG_LINE: u64 = 101;
+const RESULT_SET_PHASE: u64 = 104;
+const RESULT_PROGRESS: u64 = 105;
+
+/// How many lines of build logs are shown below the progress.
+const LOG_TAIL: usize = 5;
+/// How many running builds are listed below the progress.
+const RUNNING_BUILDS: usize = 3;
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A line of `--log-format internal-json` output, without its `@nix ` prefix.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+enum Event {
+    Start {
+        id: u64,
+        #[serde(rename = "type", default)]
+        kind: u64,
+        #[serde(default)]
+        text: String,
+        #[serde(default)]
+        fields: Vec<Value>,
+    },
+    Stop {
+        id: u64,
+    },
+    Result {
+        id: u64,
+        #[serde(rename = "type")]
+        kind: u64,
+        #[serde(default)]
+        fields: Vec<Value>,
+    },
+    Msg {
+        level: u64,
+        msg: String,
+    },
+}
+
+#[derive(Debug)]
+struct Activity {
+    kind: u64,
+    name: String,
+    phase: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Counts {
+    done: u64,
+    expected: u64,
+    running: u64,
+    failed: u64,
+}
+
+impl Counts {
+    fn from_fields(fields: &[Value]) -> Self {
+        let field = |i: usize| fields.get(i).and_then(Value::as_u64).unwrap_or_default();
+        Self {
+            done: field(0),
+            expected: field(1),
+            running: field(2),
+            failed: field(3),
+        }
+    }
+}
+
+fn strip_ansi(input: &str) -> String {
+    static ANSI: Lazy<Regex> = Lazy::new(|| Regex::new(r"\x1b\[[0-9;?]*[A-Za-z]").unwrap());
+    ANSI.replace_all(input, "").into_owned()
+}
+
+/// Turns `/nix/store/<hash>-<name>.drv` into `<name>`.
+fn store_path_name(path: &str) -> String {
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name = name.split_once('-').map(|(_, name)| name).unwrap_or(name);
+    name.strip_suffix(".drv").unwrap_or(name).to_string()
+}
+
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+fn truncate(line: &str, width: usize) -> String {
+    match line.char_indices().nth(width.saturating_sub(1)) {
+        Some((end, _)) => format!("{}…", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// Renders the activity stream of a Nix command. On a terminal the state of the build is redrawn
+/// in place, otherwise activities are logged as plain lines.
+pub struct Progress {
+    interactive: bool,
+    activities: BTreeMap<u64, Activity>,
+    builds: Counts,
+    downloads: Counts,
+    log_tail: VecDeque<String>,
+    /// Errors reported by Nix and any output that was not part of the activity stream.
+    captured: String,
+    drawn: usize,
+    last_draw: Option<Instant>,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Progress {
+    pub fn new() -> Self {
+        Self {
+            interactive: std::io::stderr().is_terminal() && log::log_enabled!(Level::Info),
+            activities: BTreeMap::new(),
+            builds: Counts::default(),
+            downloads: Counts::default(),
+            log_tail: VecDeque::new(),
+            captured: String::new(),
+            drawn: 0,
+            last_draw: None,
+        }
+    }
+
+    /// Handles a single line of output from Nix.
+    pub fn line(&mut self, line: &str) {
+        let Some(json) = line.strip_prefix("@nix ") else {
+            let line = strip_ansi(line);
+
+            self.clear();
+            error!("{line}");
+            self.captured.push_str(&line);
+            self.captured.push('\n');
+            self.draw(true);
+            return;
+        };
+
+        match serde_json::from_str::<Event>(json) {
+            Ok(event) => self.event(event),
+            Err(e) => trace!("Ignoring unknown Nix log event {json}: {e}"),
+        }
+    }
+
+    /// Removes the progress display and returns everything that was captured.
+    pub fn finish(mut self) -> String {
+        self.clear();
+        self.captured
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start {
+                id,
+                kind,
+                text,
+                fields,
+            } => {
+                let field = fields.first().and_then(Value::as_str);
+                let name = match (kind, field) {
+                    (ACTIVITY_BUILD | ACTIVITY_SUBSTITUTE, Some(path)) => store_path_name(path),
+                    _ => text,
+                };
+
+                if !self.interactive {
+                    match kind {
+                        ACTIVITY_BUILD => info!("Building {name}"),
+                        ACTIVITY_SUBSTITUTE => info!("Fetching {name}"),
+                        _ => {}
+                    }
+                }
+
+                self.activities.insert(
+                    id,
+                    Activity {
+                        kind,
+                        name,
+                        phase: None,
+                    },
+                );
+            }
+            Event::Stop { id } => {
+                self.activities.remove(&id);
+            }
+            Event::Result { id, kind, fields } => self.result(id, kind, &fields),
+            Event::Msg { level, msg } => {
+                let msg = strip_ansi(&msg);
+
+                self.clear();
+                match level {
+                    0 => {
+                        error!("{msg}");
+                        self.captured.push_str(&msg);
+                        self.captured.push('\n');
+                    }
+                    1 => warn!("{msg}"),
+                    2 | 3 => info!("{msg}"),
+                    _ => debug!("{msg}"),
+                }
+                self.draw(true);
+                return;
+            }
+        }
+
+        self.draw(false);
+    }
+
+    fn result(&mut self, id: u64, kind: u64, fields: &[Value]) {
+        let Some(activity) = self.activities.get_mut(&id) else {
+            return;
+        };
+        let field = fields.first().and_then(Value::as_str);
+
+        match (kind, field) {
+            (RESULT_BUILD_LOG_LINE, Some(line)) => {
+                let line = format!(
+                    "{}> {}",
+                    activity.name,
+                    strip_ansi(line).replace('\t', "    ")
+                );
+                if !self.interactive {
+                    debug!("{line}");
+                }
+
+                self.log_tail.push_back(line);
+                if self.log_tail.len() > LOG_TAIL {
+                    self.log_tail.pop_front();
+                }
+            }
+            (RESULT_SET_PHASE, Some(phase)) => activity.phase = Some(phase.to_string()),
+            (RESULT_PROGRESS, _) => match activity.kind {
+                ACTIVITY_BUILDS => self.builds = Counts::from_fields(fields),
+                ACTIVITY_COPY_PATHS => self.downloads = Counts::from_fields(fields),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn clear(&mut self) {
+        if self.drawn > 0 {
+            eprint!("\x1b[{}A\x1b[J", self.drawn);
+            self.drawn = 0;
+        }
+    }
+
+    fn draw(&mut self, force: bool) {
+        if !self.interactive {
+            return;
+        }
+        if !force
+            && self
+                .last_draw
+                .is_some_and(|t| t.elapsed() < REDRAW_INTERVAL)
+        {
+            return;
+        }
+
+        let width = terminal_width();
+        let mut lines = vec![];
+
+        let mut status = vec![format!(
+            "{}/{} built",
+            self.builds.done, self.builds.expected
+        )];
+        if self.builds.running > 0 {
+            status.push(
+                format!("{} running", self.builds.running)
+                    .blue()
+                    .to_string(),
+            );
+        }
+        if self.builds.failed > 0 {
+            status.push(format!("{} failed", self.builds.failed).red().to_string());
+        }
+        if self.downloads.expected > 0 {
+            status.push(format!(
+                "{}/{} fetched",
+                self.downloads.done, self.downloads.expected
+            ));
+        }
+        lines.push(format!(
+            "🍦 Nilla  {}  {}",
+            "BUILD".bold().cyan(),
+            status.join(", ")
+        ));
+
+        let running = self
+            .activities
+            .values()
+            .filter(|a| a.kind == ACTIVITY_BUILD)
+            .take(RUNNING_BUILDS);
+        for activity in running {
+            let line = match &activity.phase {
+                Some(phase) => format!("  building {} ({phase})", activity.name),
+                None => format!("  building {}", activity.name),
+            };
+            lines.push(truncate(&line, width));
+        }
+
+        for line in &self.log_tail {
+            lines.push(truncate(&format!("    {line}"), width).dimmed().to_string());
+        }
+
+        self.clear();
+        for line in &lines {
+            eprintln!("{line}");
+        }
+        self.drawn = lines.len();
+        self.last_draw = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress() -> Progress {
+        Progress {
+            interactive: false,
+            ..Progress::new()
+        }
+    }
+
+    #[test]
+    fn store_path_names() {
+        assert_eq!(
+            store_path_name("/nix/store/0c7xmkzk7g3s8z7qyh4s3frxmbi7xhb2-hello-2.12.drv"),
+            "hello-2.12"
+        );
+        assert_eq!(store_path_name("hello"), "hello");
+    }
+
+    #[test]
+    fn builds() {
+        let mut progress = progress();
+        progress.line(r#"@nix {"action":"start","id":1,"type":105,"text":"building","fields":["/nix/store/abc-hello-2.12.drv"]}"#);
+
+        let activity = &progress.activities[&1];
+        assert_eq!(activity.kind, ACTIVITY_BUILD);
+        assert_eq!(activity.name, "hello-2.12");
+
+        progress.line(r#"@nix {"action":"result","id":1,"type":104,"fields":["buildPhase"]}"#);
+        assert_eq!(progress.activities[&1].phase.as_deref(), Some("buildPhase"));
+
+        progress.line(
+            r#"@nix {"action":"result","id":1,"type":101,"fields":["\u001b[1mmake\u001b[0m\tall"]}"#,
+        );
+        assert_eq!(
+            progress.log_tail.iter().collect::<Vec<_>>(),
+            ["hello-2.12> make    all"]
+        );
+
+        progress.line(r#"@nix {"action":"stop","id":1}"#);
+        assert!(progress.activities.is_empty());
+    }
+
+    #[test]
+    fn log_tail_is_limited() {
+        let mut progress = progress();
+        progress
+            .line(r#"@nix {"action":"start","id":1,"type":105,"fields":["/nix/store/abc-a.drv"]}"#);
+        for i in 0..10 {
+            progress.line(&format!(
+                r#"@nix {{"action":"result","id":1,"type":101,"fields":["{i}"]}}"#
+            ));
+        }
+
+        assert_eq!(progress.log_tail.len(), LOG_TAIL);
+        assert_eq!(progress.log_tail.back().map(String::as_str), Some("a> 9"));
+    }
+
+    #[test]
+    fn counts() {
+        let mut progress = progress();
+        progress.line(r#"@nix {"action":"start","id":1,"type":104,"text":""}"#);
+        progress.line(r#"@nix {"action":"result","id":1,"type":105,"fields":[1,4,2,1]}"#);
+
+        let builds = progress.builds;
+        assert_eq!(
+            (builds.done, builds.expected, builds.running, builds.failed),
+            (1, 4, 2, 1)
+        );
+
+        // Results for activities that never started are ignored.
+        progress.line(r#"@nix {"action":"result","id":2,"type":105,"fields":[9,9,9,9]}"#);
+        assert_eq!(progress.builds.done, 1);
+    }
+
+    #[test]
+    fn captures_errors() {
+        let mut progress = progress();
+        progress.line(
+            r#"@nix {"action":"msg","level":0,"msg":"\u001b[31;1merror:\u001b[0m builder failed"}"#,
+        );
+        progress.line(r#"@nix {"action":"msg","level":1,"msg":"warning: dirty tree"}"#);
+        progress.line(r#"@nix {"action":"unknown","id":1}"#);
+        progress.line("@nix not json");
+        progress.line("\x1b[1mplain output\x1b[0m");
+
+        assert_eq!(progress.finish(), "error: builder failed\nplain output\n");
+    }
+
+    #[test]
+    fn truncates_lines() {
+        assert_eq!(truncate("hello", 10), "hello");
+        assert_eq!(truncate("hello world", 5), "hell…");
+        assert_eq!(strip_ansi("\x1b[1;31mred\x1b[0m"), "red");
+    }
+}
@@ -0,0 +1,223 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{anyhow, bail};
+use log::debug;
+use serde::Deserialize;
+use tokio::process::Command;
+use url::Url;
+
+#[derive(Debug, Deserialize)]
+struct SourcesFile {
+    pins: BTreeMap<String, serde_json::Value>,
+}
+
+pub fn is_available() -> bool {
+    which::which("npins").is_ok()
+}
+
+/// Fails early for commands that change pins, before any work is done.
+pub fn ensure_available() -> anyhow::Result<()> {
+    if !is_available() {
+        bail!(
+            "Managing pins requires npins, which could not be found. Install it (for example with `nix profile install nixpkgs#npins`) and make sure it is in your PATH"
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn run<P>(dir: P, args: &[&str]) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    ensure_available()?;
+
+    debug!("Running npins:\nnpins {}", args.join(" "));
+    let output = Command::new("npins")
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        bail!("npins {} failed:\n{stderr}", args[0]);
+    }
+
+    Ok(())
+}
+
+/// Reads the pinned revision (or version for pins without one) of every pin in `npins/sources.json`.
+pub fn read_revisions<P>(dir: P) -> anyhow::Result<BTreeMap<String, Option<String>>>
+where
+    P: AsRef<Path>,
+{
+    let path = dir.as_ref().join("npins").join("sources.json");
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("Could not read {}: {e}", path.display()))?;
+    let sources: SourcesFile = serde_json::from_str(&contents)?;
+
+    Ok(sources
+        .pins
+        .into_iter()
+        .map(|(name, pin)| {
+            let revision = pin
+                .get("revision")
+                .or_else(|| pin.get("version"))
+                .or_else(|| pin.get("hash"))
+                .and_then(|r| r.as_str())
+                .map(|r| r.to_string());
+            (name, revision)
+        })
+        .collect())
+}
+
+/// Converts a Nilla style source into the arguments for `npins add`.
+pub fn add_args(name: &str, source: &str) -> anyhow::Result<Vec<String>> {
+    let mut args = vec!["add".to_string(), "--name".to_string(), name.to_string()];
+
+    if let Some(channel) = source.strip_prefix("channel:") {
+        args.extend(["channel".to_string(), channel.to_string()]);
+        return Ok(args);
+    }
+
+    if source.starts_with("http://") || source.starts_with("https://") {
+        args.extend(["tarball".to_string(), source.to_string()]);
+        return Ok(args);
+    }
+
+    if let Some(tarball) = source.strip_prefix("tarball:") {
+        args.extend(["tarball".to_string(), tarball.to_string()]);
+        return Ok(args);
+    }
+
+    let (scheme, rest) = source
+        .split_once(':')
+        .ok_or_else(|| anyhow!("Could not parse source {source}"))?;
+    let url = match scheme {
+        "git" => Url::parse(source)?,
+        _ => Url::parse(&format!("{scheme}://{rest}"))?,
+    };
+    let qps = url.query_pairs();
+    let r#ref = qps.clone().find(|(k, _)| k == "ref").map(|(_, v)| v);
+    let rev = qps.clone().find(|(k, _)| k == "rev").map(|(_, v)| v);
+
+    match scheme {
+        "github" | "gitlab" => {
+            let owner = url
+                .host_str()
+                .ok_or_else(|| anyhow!("could not get owner"))?;
+            let repo = url.path().trim_matches('/');
+            if repo.is_empty() {
+                bail!("could not get repo");
+            }
+
+            args.push(scheme.to_string());
+            if scheme == "github" {
+                args.extend([owner.to_string(), repo.to_string()]);
+            } else {
+                args.push(format!("{owner}/{repo}"));
+            }
+        }
+        "git" => {
+            args.extend(["git".to_string(), url.path().to_string()]);
+        }
+        _ => bail!("Unsupported source scheme {scheme} for a pin"),
+    }
+
+    if let Some(r#ref) = r#ref {
+        args.extend(["--branch".to_string(), r#ref.to_string()]);
+    }
+    if let Some(rev) = rev {
+        args.extend(["--at".to_string(), rev.to_string()]);
+    }
+
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(source: &str) -> Vec<String> {
+        add_args("name", source).unwrap()
+    }
+
+    #[test]
+    fn channels_and_tarballs() {
+        assert_eq!(
+            args("channel:nixos-unstable"),
+            ["add", "--name", "name", "channel", "nixos-unstable"]
+        );
+        assert_eq!(
+            args("https://example.com/a.tar.gz"),
+            [
+                "add",
+                "--name",
+                "name",
+                "tarball",
+                "https://example.com/a.tar.gz"
+            ]
+        );
+        assert_eq!(
+            args("tarball:https://example.com/a.tar.gz"),
+            [
+                "add",
+                "--name",
+                "name",
+                "tarball",
+                "https://example.com/a.tar.gz"
+            ]
+        );
+    }
+
+    #[test]
+    fn forges() {
+        assert_eq!(
+            args("github:owner/repo"),
+            ["add", "--name", "name", "github", "owner", "repo"]
+        );
+        assert_eq!(
+            args("gitlab:group/repo?ref=main"),
+            [
+                "add",
+                "--name",
+                "name",
+                "gitlab",
+                "group/repo",
+                "--branch",
+                "main"
+            ]
+        );
+        assert_eq!(
+            args("github:owner/repo?ref=main&rev=abc"),
+            [
+                "add", "--name", "name", "github", "owner", "repo", "--branch", "main", "--at",
+                "abc"
+            ]
+        );
+    }
+
+    #[test]
+    fn git() {
+        assert_eq!(
+            args("git:https://example.com/repo.git?ref=main"),
+            [
+                "add",
+                "--name",
+                "name",
+                "git",
+                "https://example.com/repo.git",
+                "--branch",
+                "main"
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_sources() {
+        assert!(add_args("name", "sourcehut:~owner/repo").is_err());
+        assert!(add_args("name", "github:owner").is_err());
+        assert!(add_args("name", "nixpkgs").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+use std::path::PathBuf;
+
+use prettytable::{Attr, Cell, Row, Table, format};
+use serde::Serialize;
+
+use crate::util::project::{LoadedProject, Source};
+
+#[derive(Debug, Serialize)]
+pub struct ProjectOutput {
+    pub uri: String,
+    pub source: Source,
+    pub path: PathBuf,
+    pub hash: String,
+    pub file: PathBuf,
+}
+
+impl ProjectOutput {
+    pub fn new(project: &LoadedProject) -> Self {
+        Self {
+            uri: project.uri.clone(),
+            source: project.source.clone(),
+            path: project.entry.path.clone(),
+            hash: project.entry.hash.clone(),
+            file: project.file.clone(),
+        }
+    }
+}
+
+pub fn is_json(cli: &nilla_cli_def::Cli) -> bool {
+    cli.output == nilla_cli_def::OutputFormat::Json
+}
+
+pub fn print_json<T>(value: &T) -> anyhow::Result<()>
+where
+    T: Serialize,
+{
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Like [`print_json`], but for commands that hand stdout over to another program.
+pub fn eprint_json<T>(value: &T) -> anyhow::Result<()>
+where
+    T: Serialize,
+{
+    eprintln!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+pub fn print_table(columns: &[&str], rows: Vec<Vec<String>>) {
+    let mut table = Table::new();
+
+    table.set_format(*format::consts::FORMAT_BOX_CHARS);
+
+    table.add_row(Row::new(
+        columns
+            .iter()
+            .map(|text| Cell::new(text).with_style(Attr::Bold))
+            .collect(),
+    ));
+
+    for row in rows {
+        table.add_row(Row::new(row.iter().map(|text| Cell::new(text)).collect()));
+    }
+
+    table.printstd();
+}
@@ -1,22 +1,32 @@
+pub mod schemes;
+
 use std::{
-    borrow::Cow,
+    collections::BTreeSet,
     path::{Path, PathBuf},
-    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use anyhow::{anyhow, bail};
+use anyhow::bail;
 use log::{debug, info, trace, warn};
-use serde::Serialize;
-use url::Url;
+use serde::{Deserialize, Serialize};
 
 use super::nix::FixedOutputStoreEntry;
 use crate::util::{
+    cache, config,
+    errors::ResolutionError,
     git,
-    nix::{self, EvalResult},
-    search::{search_up_for_dir, search_up_for_file},
+    lock::{Lock, LockedSource},
+    nix::{
+        self, EvalResult,
+        expr::{self, AttrPath},
+    },
+    project::schemes::{SchemeRegistry, SourceSpec},
+    registry,
+    search::search_up_for_file,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
 pub enum Source {
     Path {
         info: PathInfo,
@@ -31,7 +41,7 @@ pub enum Source {
         entry: FixedOutputStoreEntry,
     },
     Tarball {
-        url: String,
+        info: TarballInfo,
         entry: FixedOutputStoreEntry,
     },
 }
@@ -42,7 +52,7 @@ impl Source {
             Source::Path { info: _, entry } => entry.path,
             Source::Git { info: _, entry } => entry.path,
             Source::Sourcehut { info: _, entry } => entry.path,
-            Source::Tarball { url: _, entry } => entry.path,
+            Source::Tarball { info: _, entry } => entry.path,
         }
     }
 
@@ -51,7 +61,7 @@ impl Source {
             Source::Path { info, entry: _ } => info.dir.unwrap_or("".into()).into(),
             Source::Git { info, entry: _ } => info.dir.unwrap_or("".into()).into(),
             Source::Sourcehut { info, entry: _ } => info.dir.unwrap_or("".into()).into(),
-            Source::Tarball { url: _, entry: _ } => PathBuf::from(""),
+            Source::Tarball { info, entry: _ } => info.dir.unwrap_or("".into()).into(),
         }
     }
 
@@ -60,7 +70,7 @@ impl Source {
             Source::Path { info: _, entry } => entry.hash,
             Source::Git { info: _, entry } => entry.hash,
             Source::Sourcehut { info: _, entry } => entry.hash,
-            Source::Tarball { url: _, entry } => entry.hash,
+            Source::Tarball { info: _, entry } => entry.hash,
         }
     }
 
@@ -69,14 +79,17 @@ impl Source {
             Source::Path { info: _, entry } => entry,
             Source::Git { info: _, entry } => entry,
             Source::Sourcehut { info: _, entry } => entry,
-            Source::Tarball { url: _, entry } => entry,
+            Source::Tarball { info: _, entry } => entry,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct PathInfo {
     pub dir: Option<String>,
+    /// The state of the repository when the project is inside a git working tree.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub git: Option<git::GitStatus>,
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -88,7 +101,7 @@ pub struct GitInfo {
     pub submodules: bool,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct GitXInfo {
     pub owner: String,
     pub repo: String,
@@ -100,6 +113,14 @@ pub struct GitXInfo {
     pub method: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct TarballInfo {
+    pub url: String,
+    pub dir: Option<String>,
+    /// The expected NAR hash of the unpacked tarball.
+    pub hash: Option<String>,
+}
+
 impl From<GitXInfo> for GitInfo {
     fn from(value: GitXInfo) -> Self {
         GitInfo {
@@ -116,6 +137,224 @@ impl From<GitXInfo> for GitInfo {
     }
 }
 
+impl GitXInfo {
+    /// Converts to [`GitInfo`] using SourceHut's `~owner/repo` URLs, which have no `.git` suffix.
+    pub fn to_sourcehut_git_info(&self) -> GitInfo {
+        GitInfo {
+            url: if self.method == "ssh" {
+                format!("git@{}:~{}/{}", self.host, self.owner, self.repo)
+            } else {
+                format!("https://{}/~{}/{}", self.host, self.owner, self.repo)
+            },
+            rev: self.rev.clone(),
+            r#ref: self.r#ref.clone(),
+            dir: self.dir.clone(),
+            submodules: self.submodules,
+        }
+    }
+}
+
+/// A resolved project along with everything needed to evaluate its `nilla.nix`.
+#[derive(Debug, Clone)]
+pub struct LoadedProject {
+    pub uri: String,
+    pub source: Source,
+    pub entry: FixedOutputStoreEntry,
+    /// The directory of the project within the source.
+    pub subpath: PathBuf,
+    /// The path to `nilla.nix` relative to the source.
+    pub file: PathBuf,
+    /// The absolute path to `nilla.nix` in the store.
+    pub path: PathBuf,
+    pub system: Option<String>,
+}
+
+impl LoadedProject {
+    pub async fn load(uri: &str) -> anyhow::Result<Self> {
+        debug!("Resolving project {uri}");
+        let source = match resolve(uri).await {
+            Ok(source) => source,
+            Err(error) => {
+                return Err(ResolutionError {
+                    uri: uri.to_string(),
+                    error,
+                }
+                .into());
+            }
+        };
+
+        let entry = source.clone().get_entry();
+        let subpath = source.clone().get_subpath();
+        let file = subpath.join("nilla.nix");
+        let path = entry.path.join(&file);
+
+        debug!("Resolved project {path:?}");
+
+        match path.try_exists() {
+            Ok(false) | Err(_) => bail!("Could not find {} in project {uri}", file.display()),
+            _ => {}
+        }
+
+        Ok(Self {
+            uri: uri.to_string(),
+            source,
+            entry,
+            subpath,
+            file,
+            path,
+            system: None,
+        })
+    }
+
+    /// Selects the system to use for this project, detecting the current one if none is given.
+    pub async fn select_system(&mut self, system: Option<&str>) -> anyhow::Result<String> {
+        let system = match system {
+            Some(s) => s.to_string(),
+            None => match nix::get_system().await {
+                Ok(s) => s,
+                Err(e) => bail!("{e:?}"),
+            },
+        };
+
+        Ok(self.system.insert(system).clone())
+    }
+
+    pub fn file_str(&self) -> &str {
+        self.file.to_str().unwrap_or("nilla.nix")
+    }
+
+    /// Binds `source` and `project`, as well as `system` if one has been selected.
+    pub fn preamble(&self) -> expr::Let {
+        let preamble = expr::import_project(&self.entry, self.file_str());
+
+        match &self.system {
+            Some(system) => preamble.bind("system", expr::string(system)),
+            None => preamble,
+        }
+    }
+
+    pub async fn evaluate(&self, body: &str, opts: nix::EvalOpts) -> anyhow::Result<EvalResult> {
+        nix::evaluate(&self.preamble().body(body), opts).await
+    }
+
+    /// Gathers everything commands need to know about an attribute in a single evaluation.
+    /// Store paths are only included with `paths`, as they force the derivation to be evaluated.
+    pub async fn inspect(
+        &self,
+        attribute: &AttrPath,
+        paths: bool,
+    ) -> anyhow::Result<AttributeInfo> {
+        match self
+            .inspect_all(std::slice::from_ref(attribute), paths)
+            .await?
+            .pop()
+        {
+            Some(info) => Ok(info),
+            None => bail!("Got no information about {attribute}"),
+        }
+    }
+
+    /// Like [`Self::inspect`], but for several attributes at once.
+    pub async fn inspect_all(
+        &self,
+        attributes: &[AttrPath],
+        paths: bool,
+    ) -> anyhow::Result<Vec<AttributeInfo>> {
+        trace!("Inspecting {attributes:?}");
+
+        let infos = attributes
+            .iter()
+            .map(|attribute| format!("({})", inspect_expr(attribute, paths)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let code = self.preamble().body(&format!("[ {infos} ]"));
+
+        let result = nix::evaluate(
+            &code,
+            nix::EvalOpts {
+                json: true,
+                raw: false,
+                impure: false,
+            },
+        )
+        .await?;
+
+        match result {
+            EvalResult::Json(value) => Ok(serde_json::from_value(value)?),
+            EvalResult::Raw(_) => bail!("Got raw, expected JSON"),
+        }
+    }
+}
+
+fn inspect_expr(attribute: &AttrPath, paths: bool) -> String {
+    let paths = match paths {
+        true => "isDerivation",
+        false => "false",
+    };
+
+    expr::Let::new()
+        .bind("exists", expr::has_attr("project", attribute))
+        .bind(
+            "target",
+            format!(
+                "if exists then {} else null",
+                expr::select("project", attribute)
+            ),
+        )
+        .bind("isAttrs", "exists && builtins.isAttrs target")
+        .bind(
+            "isDerivation",
+            "isAttrs && (target.type or null) == \"derivation\"",
+        )
+        .bind("paths", paths)
+        .bind(
+            "outputs",
+            "if isDerivation then target.outputs or [ \"out\" ] else [ ]",
+        )
+        .body(
+            "{
+                inherit exists outputs;
+                name = if isAttrs then target.name or null else null;
+                type = if isAttrs then target.type or null else null;
+                mainProgram = if isDerivation then target.meta.mainProgram or null else null;
+                drvPath = if paths then target.drvPath else null;
+                outPath = if paths then target.outPath else null;
+                outPaths = if paths then map (output: target.${output}.outPath) outputs else [ ];
+            }",
+        )
+}
+
+/// Turns a package name given on the command line into the attribute to use for `system`.
+/// Attribute paths are used as they are, except for `packages.<name>` and `shells.<name>` which
+/// are short for their result for `system`.
+pub fn package_attribute(name: &str, system: &str) -> anyhow::Result<AttrPath> {
+    if !name.contains('.') {
+        return Ok(AttrPath::new(["packages", name, "result", system]));
+    }
+
+    let attribute = AttrPath::parse(name)?;
+
+    match attribute.segments() {
+        [kind, name] if kind == "packages" || kind == "shells" => {
+            Ok(AttrPath::new([kind, name, "result", system]))
+        }
+        _ => Ok(attribute),
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttributeInfo {
+    pub exists: bool,
+    pub name: Option<String>,
+    pub r#type: Option<String>,
+    pub main_program: Option<String>,
+    pub drv_path: Option<String>,
+    pub out_path: Option<String>,
+    pub outputs: Vec<String>,
+    pub out_paths: Vec<String>,
+}
+
 pub fn remove_filename_from_path<P>(path: P) -> PathBuf
 where
     P: Into<PathBuf>,
@@ -140,34 +379,176 @@ where
     path
 }
 
-async fn resolve_git(info: GitInfo) -> anyhow::Result<Source> {
+/// Finds the directory containing `nilla.nix` for a project that lives on the local filesystem.
+pub fn find_local_project(uri: &str) -> anyhow::Result<PathBuf> {
+    let uri = registry::expand(uri)?;
+
+    match SchemeRegistry::default().parse(&uri)? {
+        SourceSpec::Path { path } => find_project_dir(&path),
+        _ => bail!("Project {uri} is not a local path"),
+    }
+}
+
+/// Searches `path` and its parents for the directory containing `nilla.nix`.
+fn find_project_dir(path: &str) -> anyhow::Result<PathBuf> {
+    let Ok(real_path) = PathBuf::from(path).canonicalize() else {
+        bail!("Could not find path {path}");
+    };
+    debug!("Found path {}", real_path.display());
+
+    let dir_path = remove_filename_from_path(real_path);
+
+    let Some(resolved_path) = search_up_for_file(&dir_path, "nilla.nix") else {
+        bail!("Could not find nilla.nix in {dir_path:?}");
+    };
+
+    Ok(remove_filename_from_path(resolved_path))
+}
+
+/// How long resolutions of sources that are not pinned to a revision are reused for.
+const SOURCE_CACHE_TTL: u64 = 60 * 60;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedSource {
+    entry: FixedOutputStoreEntry,
+    #[serde(default)]
+    rev: Option<String>,
+    fetched_at: u64,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Looks up a previous resolution of a source. Sources pinned to a revision never expire,
+/// everything else is refetched once `SOURCE_CACHE_TTL` has passed.
+fn get_cached_source(key: &str, pinned: bool) -> Option<CachedSource> {
+    let cli = config::cli();
+    if cli.refresh {
+        debug!("Refreshing source, ignoring cached resolution");
+        return None;
+    }
+
+    // Updating the lock file has to record the latest revision, not one that was cached earlier.
+    if cli.update_lock && !pinned {
+        debug!("Updating the lock file, ignoring cached resolution");
+        return None;
+    }
+
+    let cached: CachedSource = serde_json::from_str(&cache::read("sources", key)?).ok()?;
+
+    if !pinned && now().saturating_sub(cached.fetched_at) > SOURCE_CACHE_TTL {
+        debug!("Cached resolution has expired");
+        return None;
+    }
+
+    // The store path may have been garbage collected since it was cached.
+    if !cached.entry.path.exists() {
+        debug!("Cached store path {:?} no longer exists", cached.entry.path);
+        return None;
+    }
+
+    debug!("Using cached resolution {:?}", cached.entry.path);
+    Some(cached)
+}
+
+fn set_cached_source(key: &str, entry: &FixedOutputStoreEntry, rev: Option<&str>) {
+    let cached = CachedSource {
+        entry: entry.clone(),
+        rev: rev.map(String::from),
+        fetched_at: now(),
+    };
+
+    let result = serde_json::to_string(&cached)
+        .map_err(anyhow::Error::from)
+        .and_then(|value| cache::write("sources", key, &value));
+
+    if let Err(e) = result {
+        warn!("Failed to cache source resolution: {e}");
+    }
+}
+
+fn check_locked_hash(
+    name: &str,
+    entry: &FixedOutputStoreEntry,
+    locked: Option<&LockedSource>,
+) -> anyhow::Result<()> {
+    match locked {
+        Some(locked) if locked.hash != entry.hash => bail!(
+            "Hash of {name} does not match the lock file, expected {} but got {}. Run with --update-lock to accept the new hash",
+            locked.hash,
+            entry.hash
+        ),
+        _ => Ok(()),
+    }
+}
+
+async fn resolve_git(mut info: GitInfo, locked: Option<&LockedSource>) -> anyhow::Result<Source> {
     debug!("Resolving git for {info:?}");
-    let code = format!(
-        "
-		let
-			info = builtins.fromJSON ''{}'';
-		in
+
+    if info.rev.is_none()
+        && let Some(rev) = locked.and_then(|l| l.rev.clone())
+    {
+        debug!("Using locked revision {rev}");
+        info.rev = Some(rev);
+    }
+
+    let cache_key = cache::key([
+        "git",
+        &info.url,
+        info.rev.as_deref().unwrap_or_default(),
+        info.r#ref.as_deref().unwrap_or_default(),
+        if info.submodules { "submodules" } else { "" },
+    ]);
+
+    if let Some(cached) = get_cached_source(&cache_key, info.rev.is_some()) {
+        check_locked_hash(&info.url, &cached.entry, locked)?;
+        info.rev = info.rev.or(cached.rev);
+        return Ok(Source::Git {
+            info,
+            entry: cached.entry,
+        });
+    }
+
+    let code = expr::Let::new()
+        .bind(
+            "info",
+            format!(
+                "builtins.fromJSON {}",
+                expr::string(&serde_json::to_string(&info).unwrap())
+            ),
+        )
+        .bind(
+            "result",
+            "
 			builtins.fetchGit (
-				{{ url = info.url; }}
-				// (if info.rev != null then {{ rev = info.rev; }} else {{}})
-				// (if info.ref != null then {{ ref = info.ref; }} else {{}})
-				// (if info.submodules != null then {{ submodules = info.submodules; }} else {{}})
+				{ url = info.url; }
+				// (if info.rev != null then { rev = info.rev; } else {})
+				// (if info.ref != null then { ref = info.ref; } else {})
+				// (if info.submodules != null then { submodules = info.submodules; } else {})
 			)
 	",
-        serde_json::to_string(&info).unwrap()
-    );
+        )
+        .body("{ path = result.outPath; rev = result.rev or null; }");
 
     let root = nix::evaluate(
         &code,
         nix::EvalOpts {
             impure: true,
             json: true,
+            raw: false,
         },
     )
     .await;
 
-    let root_path = match root {
-        Ok(EvalResult::Json(res)) => res.as_str().unwrap().to_string(),
+    let (root_path, rev) = match root {
+        Ok(EvalResult::Json(res)) => (
+            res["path"].as_str().unwrap().to_string(),
+            res["rev"].as_str().map(String::from),
+        ),
         Ok(EvalResult::Raw(_)) => {
             bail!("Got raw, expected JSON");
         }
@@ -184,63 +565,176 @@ async fn resolve_git(info: GitInfo) -> anyhow::Result<Source> {
 
     let final_path = paths[0].clone();
 
-    return Ok(Source::Git {
-        info,
-        entry: FixedOutputStoreEntry {
-            path: final_path.clone(),
-            hash: nix::get_store_hash(&final_path).await?,
-        },
-    });
+    let entry = FixedOutputStoreEntry {
+        path: final_path.clone(),
+        hash: nix::get_store_hash(&final_path).await?,
+    };
+    check_locked_hash(&info.url, &entry, locked)?;
+    set_cached_source(&cache_key, &entry, rev.as_deref());
+
+    info.rev = info.rev.or(rev);
+
+    Ok(Source::Git { info, entry })
 }
 
-async fn resolve_git_path<P>(path: P, project: P) -> anyhow::Result<Source>
-where
-    P: AsRef<Path>,
-{
-    let path: &Path = path.as_ref();
-    let project: &Path = project.as_ref();
-    debug!("Resolving git path for {path:?}");
+async fn fetch_git_path(path: &Path, submodules: bool) -> anyhow::Result<String> {
+    let code = expr::Let::new()
+        .bind(
+            "path",
+            format!("builtins.toPath {}", expr::string(path.to_str().unwrap())),
+        )
+        .bind("submodules", submodules.to_string())
+        .body("builtins.fetchGit { url = path; inherit submodules; }");
 
-    let untracked = git::get_untracked_files(path).await?;
+    let root = nix::evaluate(
+        &code,
+        nix::EvalOpts {
+            impure: true,
+            json: true,
+            raw: false,
+        },
+    )
+    .await;
 
-    if !untracked.is_empty() {
-        warn!("Untracked files in {path:?} will not be available within Nix");
-        for file in untracked {
-            warn!("  {}", file.to_str().unwrap());
+    match root {
+        Ok(EvalResult::Json(res)) => Ok(res.as_str().unwrap().to_string()),
+        Ok(EvalResult::Raw(_)) => {
+            bail!("Got raw, expected JSON");
         }
-        warn!("");
-        warn!(
-            "If you experience issues, try adding these files to your git repository with `git add`"
+        Err(e) => {
+            bail!("{e}");
+        }
+    }
+}
+
+/// Copies the files git knows about from a repository's working tree to the store.
+async fn add_worktree_to_store(
+    path: &Path,
+    untracked: bool,
+    submodules: bool,
+) -> anyhow::Result<String> {
+    let files = git::get_worktree_files(path, untracked, submodules).await?;
+
+    // Directories need to be let through the filter for the files within them to be copied.
+    let mut allowed = BTreeSet::new();
+    for file in &files {
+        allowed.extend(
+            file.ancestors()
+                .filter_map(|p| p.to_str())
+                .filter(|p| !p.is_empty()),
         );
     }
 
-    let code = format!(
-        "
-		let
-      path = builtins.toPath \"{}\";
-		in
-			builtins.fetchGit path
-	",
-        path.to_str().unwrap()
-    );
+    // The list is passed through a file since it can easily exceed the size limit of arguments.
+    let list_file =
+        std::env::temp_dir().join(format!("nilla-files-{}.json", rand::random::<u32>()));
+    std::fs::write(&list_file, serde_json::to_string(&allowed)?)?;
+
+    let root = path.to_str().unwrap();
+    let code = expr::Let::new()
+        .bind("root", expr::string(root))
+        .bind(
+            "allowed",
+            format!(
+                "builtins.listToAttrs (map (name: {{ inherit name; value = true; }}) (builtins.fromJSON (builtins.readFile {})))",
+                expr::string(list_file.to_str().unwrap())
+            ),
+        )
+        .body(
+            "builtins.path {
+                name = \"source\";
+                path = root;
+                filter = path: type:
+                    let
+                        absolute = toString path;
+                        relative = builtins.substring (builtins.stringLength root + 1) (builtins.stringLength absolute) absolute;
+                    in
+                        allowed ? ${relative};
+            }",
+        );
 
     let root = nix::evaluate(
         &code,
         nix::EvalOpts {
             impure: true,
             json: true,
+            raw: false,
         },
     )
     .await;
 
-    let root_path = match root {
-        Ok(EvalResult::Json(res)) => res.as_str().unwrap().to_string(),
+    if let Err(e) = std::fs::remove_file(&list_file) {
+        debug!("Failed to remove {list_file:?}: {e}");
+    }
+
+    match root {
+        Ok(EvalResult::Json(res)) => Ok(res.as_str().unwrap().to_string()),
         Ok(EvalResult::Raw(_)) => {
             bail!("Got raw, expected JSON");
         }
-        _ => {
-            bail!("{}", root.unwrap_err());
+        Err(e) => {
+            bail!("{e}");
+        }
+    }
+}
+
+async fn resolve_git_path(repository: &git::Repository, project: &Path) -> anyhow::Result<Source> {
+    let path = repository.root.as_path();
+    debug!("Resolving git {} path for {path:?}", repository.layout);
+
+    let status = git::get_status(repository).await?;
+    let untracked = git::get_untracked_files(path).await?;
+    let include_untracked = config::cli().include_untracked;
+    let include_submodules = config::cli().include_submodules;
+
+    let rev = status.rev.as_deref().unwrap_or("no commits");
+    match &status.branch {
+        Some(branch) => info!("Using {path:?} at {rev} on {branch}"),
+        None => info!("Using {path:?} at {rev} (detached)"),
+    }
+
+    if status.dirty {
+        warn!("Uncommitted changes in {path:?} will be included");
+        for file in &status.modified {
+            warn!("  {}", file.display());
+        }
+    }
+
+    if config::cli().require_clean {
+        if status.dirty {
+            bail!(
+                "{path:?} has uncommitted changes, commit or stash them before using --require-clean"
+            );
+        }
+        if !untracked.is_empty() && include_untracked {
+            bail!("{path:?} has untracked files, add or remove them before using --require-clean");
+        }
+    }
+
+    if !untracked.is_empty() && include_untracked {
+        info!("Including untracked files from {path:?}");
+        for file in &untracked {
+            info!("  {}", file.display());
         }
+    } else if !untracked.is_empty() {
+        warn!("Untracked files in {path:?} will not be available within Nix");
+        for file in &untracked {
+            warn!("  {}", file.to_str().unwrap());
+        }
+        warn!("");
+        warn!(
+            "If you experience issues, try adding these files to your git repository with `git add`, or pass --include-untracked"
+        );
+    }
+
+    // Nix cannot always fetch worktrees and submodules, whose `.git` is a file pointing elsewhere,
+    // so their files are copied from the working tree instead.
+    let root_path = if (!untracked.is_empty() && include_untracked)
+        || repository.layout != git::Layout::Repository
+    {
+        add_worktree_to_store(path, include_untracked, include_submodules).await?
+    } else {
+        fetch_git_path(path, include_submodules).await?
     };
 
     let store_path = nix::realise(root_path).await;
@@ -257,7 +751,10 @@ where
     };
 
     Ok(Source::Path {
-        info: PathInfo { dir },
+        info: PathInfo {
+            dir,
+            git: Some(status),
+        },
         entry: FixedOutputStoreEntry {
             path: final_path.clone(),
             hash: nix::get_store_hash(final_path).await?,
@@ -265,21 +762,38 @@ where
     })
 }
 
-async fn resolve_tar(url: &str) -> anyhow::Result<Source> {
-    debug!("Resolving tarball at {url:?}");
-    let code = format!(
-        "
-		builtins.fetchTarball {{
-			url = \"{url}\";
-		}}
-	"
-    );
+async fn resolve_tar(info: TarballInfo, locked: Option<&LockedSource>) -> anyhow::Result<Source> {
+    debug!("Resolving tarball for {info:?}");
+    let url = info.url.as_str();
+    let expected_hash = info
+        .hash
+        .as_deref()
+        .or_else(|| locked.map(|l| l.hash.as_str()));
+    let cache_key = cache::key(["tarball", url, expected_hash.unwrap_or_default()]);
+
+    if let Some(cached) = get_cached_source(&cache_key, expected_hash.is_some()) {
+        check_locked_hash(url, &cached.entry, locked)?;
+        return Ok(Source::Tarball {
+            info,
+            entry: cached.entry,
+        });
+    }
+
+    let code = match expected_hash {
+        Some(hash) => format!(
+            "builtins.fetchTarball {{ url = {}; sha256 = {}; }}",
+            expr::string(url),
+            expr::string(hash)
+        ),
+        None => format!("builtins.fetchTarball {{ url = {}; }}", expr::string(url)),
+    };
 
     let root = nix::evaluate(
         code.trim(),
         nix::EvalOpts {
             impure: true,
             json: true,
+            raw: false,
         },
     )
     .await;
@@ -300,291 +814,107 @@ async fn resolve_tar(url: &str) -> anyhow::Result<Source> {
         bail!("{}", store_path.unwrap_err());
     };
 
-    return Ok(Source::Tarball {
-        url: url.to_string(),
-        entry: FixedOutputStoreEntry {
-            path: paths[0].clone(),
-            hash: nix::get_store_hash(&paths[0]).await?,
-        },
-    });
-}
-
-pub async fn resolve(uri: &str) -> anyhow::Result<Source> {
-    info!("Looking for project at {uri}");
-
-    trace!("Trying {uri} as local path");
-    if uri.starts_with(".") || uri.starts_with("/") || uri.starts_with("~") {
-        if let Ok(real_path) = {
-            let Ok(path) = PathBuf::from_str(uri);
-            path.canonicalize()
-        } {
-            debug!("Found path {}", real_path.display());
-
-            let dir_path = remove_filename_from_path(real_path.clone());
-
-            let Some(resolved_path) = search_up_for_file(&dir_path, "nilla.nix") else {
-                bail!("Could not find nilla.nix in {dir_path:?}");
-            };
+    let entry = FixedOutputStoreEntry {
+        path: paths[0].clone(),
+        hash: nix::get_store_hash(&paths[0]).await?,
+    };
+    check_locked_hash(url, &entry, locked)?;
+    set_cached_source(&cache_key, &entry, None);
 
-            let resolved_dir_path = remove_filename_from_path(resolved_path.clone());
+    Ok(Source::Tarball { info, entry })
+}
 
-            let git_dir = search_up_for_dir(&resolved_dir_path, ".git");
+async fn resolve_path(path: &str) -> anyhow::Result<Source> {
+    let resolved_dir_path = find_project_dir(path)?;
 
-            if let Some(dir) = git_dir {
-                let resolved_git_dir = remove_directory_from_path(dir.clone());
+    if let Some(repository) = git::find_repository(&resolved_dir_path).await? {
+        if repository.layout == git::Layout::Bare {
+            bail!(
+                "{resolved_dir_path:?} is inside the bare git repository {:?}, which has no working tree. Check it out with `git worktree add`, or use git:file://{} to build a commit",
+                repository.root,
+                repository.root.display()
+            );
+        }
 
-                let source = resolve_git_path(&resolved_git_dir, &resolved_dir_path).await?;
+        resolve_git_path(&repository, &resolved_dir_path).await
+    } else {
+        match nix::add_to_store(&resolved_dir_path).await {
+            Ok(entry) => {
+                debug!("Added {resolved_dir_path:?} to store as {:?}", entry.path);
+
+                Ok(Source::Path {
+                    info: PathInfo {
+                        dir: None,
+                        git: None,
+                    },
+                    entry,
+                })
+            }
+            _ => {
+                bail!("Could not add {resolved_dir_path:?} to store");
+            }
+        }
+    }
+}
 
-                return Ok(source);
-            } else {
-                match nix::add_to_store(&resolved_dir_path).await {
-                    Ok(entry) => {
-                        debug!("Added {real_path:?} to store as {:?}", entry.path);
-
-                        return Ok(Source::Path {
-                            info: PathInfo { dir: None },
-                            entry,
-                        });
-                    }
-                    _ => {
-                        bail!("Could not add {real_path:?} to store");
-                    }
+impl SourceSpec {
+    pub async fn resolve(self, locked: Option<&LockedSource>) -> anyhow::Result<Source> {
+        match self {
+            SourceSpec::Path { path } => resolve_path(&path).await,
+            SourceSpec::Git(info) => resolve_git(info, locked).await,
+            SourceSpec::Sourcehut(info) => {
+                match resolve_git(info.to_sourcehut_git_info(), locked).await? {
+                    Source::Git { info: git, entry } => Ok(Source::Sourcehut {
+                        info: GitXInfo {
+                            rev: git.rev,
+                            ..info
+                        },
+                        entry,
+                    }),
+                    source => Ok(source),
                 }
             }
-        } else {
-            bail!("Could not find path {uri}");
+            SourceSpec::Tarball(info) => resolve_tar(info, locked).await,
         }
     }
-    if uri.starts_with("path:") {
-        if let Ok(real_path) = {
-            let Ok(path) = PathBuf::from_str(&uri[4..]);
-            path.canonicalize()
-        } {
-            debug!("Found path {}", real_path.display());
+}
 
-            let dir_path = remove_filename_from_path(real_path.clone());
+pub async fn resolve(uri: &str) -> anyhow::Result<Source> {
+    resolve_with(&SchemeRegistry::default(), uri).await
+}
 
-            let Some(resolved_path) = search_up_for_file(&dir_path, "nilla.nix") else {
-                bail!("Could not find nilla.nix in {dir_path:?}");
-            };
+/// Resolves a project URI using the schemes in `registry`.
+pub async fn resolve_with(registry: &SchemeRegistry, uri: &str) -> anyhow::Result<Source> {
+    info!("Looking for project at {uri}");
 
-            let resolved_dir_path = remove_filename_from_path(resolved_path.clone());
+    let uri = &registry::expand(uri)?;
+    let spec = registry.parse(uri)?;
+    trace!("Parsed {uri} as {spec:?}");
 
-            let git_dir = search_up_for_dir(&resolved_dir_path, ".git");
+    // Local paths are always resolved from their current contents.
+    if let SourceSpec::Path { .. } = spec {
+        return spec.resolve(None).await;
+    }
 
-            if let Some(dir) = git_dir {
-                let resolved_git_dir = remove_directory_from_path(dir.clone());
+    let mut lock = Lock::from_cli()?;
+    let locked = lock.as_ref().and_then(|l| l.get(uri)).cloned();
 
-                let source = resolve_git_path(&resolved_git_dir, &resolved_dir_path).await?;
+    let source = spec.resolve(locked.as_ref()).await?;
 
-                return Ok(source);
-            } else {
-                match nix::add_to_store(&resolved_dir_path).await {
-                    Ok(entry) => {
-                        debug!("Added {real_path:?} to store as {:?}", entry.path);
-
-                        return Ok(Source::Path {
-                            info: PathInfo { dir: None },
-                            entry,
-                        });
-                    }
-                    _ => {
-                        bail!("Could not add {real_path:?} to store");
-                    }
-                }
-            }
-        } else {
-            bail!("Could not find path {}", &uri[4..])
+    if let Some(lock) = &mut lock {
+        let rev = match &source {
+            Source::Git { info, .. } => info.rev.clone(),
+            Source::Sourcehut { info, .. } => info.rev.clone(),
+            _ => None,
         };
+        lock.record(
+            uri,
+            LockedSource {
+                rev,
+                hash: source.clone().get_hash(),
+            },
+        )?;
     }
 
-    trace!("Trying as URL");
-    if uri.starts_with("git:") {
-        trace!("matched as git");
-        let url = Url::parse(uri).unwrap();
-        let qps = url.query_pairs();
-        let info = GitInfo {
-            url: url.path().to_string(),
-            rev: qps
-                .clone()
-                .find(|(k, _)| k == "rev")
-                .map(|(_, v)| v.to_string()),
-            r#ref: qps
-                .clone()
-                .find(|(k, _)| k == "ref")
-                .map(|(_, v)| v.to_string()),
-            dir: qps
-                .clone()
-                .find(|(k, _)| k == "dir")
-                .map(|(_, v)| v.to_string()),
-            submodules: qps
-                .clone()
-                .find(|(k, _)| k == "submodules")
-                .unwrap_or(("".into(), "false".into()))
-                .1
-                == "true",
-        };
-        Ok(resolve_git(info).await?)
-    } else if let Some(minus_github) = uri.strip_prefix("github:") {
-        trace!("matched as github");
-        let url = Url::parse(&format!("github://{}", minus_github)).unwrap();
-        let mut parsed = url
-            .path_segments()
-            .ok_or_else(|| anyhow!("cannot be base"))?;
-        let owner = url.host().unwrap().to_string();
-        let repo = parsed
-            .next()
-            .ok_or_else(|| anyhow!("could not get repo"))?
-            .to_string();
-
-        let qps = url.query_pairs();
-
-        let info = GitXInfo {
-            owner,
-            repo,
-            r#ref: qps
-                .clone()
-                .find(|(k, _)| k == "ref")
-                .map(|(_, v)| v.to_string()),
-            rev: qps
-                .clone()
-                .find(|(k, _)| k == "rev")
-                .map(|(_, v)| v.to_string()),
-            dir: qps
-                .clone()
-                .find(|(k, _)| k == "dir")
-                .map(|(_, v)| v.to_string()),
-            host: qps
-                .clone()
-                .find(|(k, _)| k == "host")
-                .unwrap_or((Cow::from(""), Cow::from("github.com")))
-                .1
-                .to_string(),
-            submodules: qps
-                .clone()
-                .find(|(k, _)| k == "submodules")
-                .unwrap_or(("".into(), "false".into()))
-                .1
-                == "true",
-            method: qps
-                .clone()
-                .find(|(k, _)| k == "method")
-                .unwrap_or(("".into(), "https".into()))
-                .1
-                .to_string(),
-        };
-        Ok(resolve_git(info.into()).await?)
-    } else if let Some(minus_gitlab) = uri.strip_prefix("gitlab:") {
-        trace!("matched as gitlab");
-        let url = Url::parse(&format!("gitlab://{}", minus_gitlab)).unwrap();
-        let mut parsed = url
-            .path_segments()
-            .ok_or_else(|| anyhow!("cannot be base"))?;
-        let owner = url.host().unwrap().to_string();
-        let repo = parsed
-            .next()
-            .ok_or_else(|| anyhow!("could not get repo"))?
-            .to_string();
-
-        let qps = url.query_pairs();
-
-        let info = GitXInfo {
-            owner,
-            repo,
-            r#ref: qps
-                .clone()
-                .find(|(k, _)| k == "ref")
-                .map(|(_, v)| v.to_string()),
-            rev: qps
-                .clone()
-                .find(|(k, _)| k == "rev")
-                .map(|(_, v)| v.to_string()),
-            dir: qps
-                .clone()
-                .find(|(k, _)| k == "dir")
-                .map(|(_, v)| v.to_string()),
-            host: qps
-                .clone()
-                .find(|(k, _)| k == "host")
-                .unwrap_or((Cow::from(""), Cow::from("gitlab.com")))
-                .1
-                .to_string(),
-            submodules: qps
-                .clone()
-                .find(|(k, _)| k == "submodules")
-                .unwrap_or(("".into(), "false".into()))
-                .1
-                == "true",
-            method: qps
-                .clone()
-                .find(|(k, _)| k == "method")
-                .unwrap_or(("".into(), "https".into()))
-                .1
-                .to_string(),
-        };
-
-        Ok(resolve_git(info.into()).await?)
-    } else if let Some(minus_tar) = uri.strip_prefix("tarball:") {
-        trace!("matched as tarball");
-        let mut minus_tar = minus_tar.to_string();
-        if !minus_tar.starts_with("http://") && !minus_tar.starts_with("https://") {
-            minus_tar = format!("http://{minus_tar}");
-        }
-        Ok(resolve_tar(&minus_tar).await?)
-    } else if uri.starts_with("http://") || uri.starts_with("https://") {
-        trace!("matched as http(s)");
-        Ok(resolve_tar(uri).await?)
-    } else if let Some(minus_tangled) = uri.strip_prefix("tangled:") {
-        trace!("matched as tangled");
-        let url = Url::parse(&format!("tangled://{}", minus_tangled)).unwrap();
-        let mut parsed = url
-            .path_segments()
-            .ok_or_else(|| anyhow!("cannot be base"))?;
-        let owner = url.host().unwrap().to_string();
-        let repo = parsed
-            .next()
-            .ok_or_else(|| anyhow!("could not get repo"))?
-            .to_string();
-
-        let qps = url.query_pairs();
-
-        let info = GitXInfo {
-            owner,
-            repo,
-            r#ref: qps
-                .clone()
-                .find(|(k, _)| k == "ref")
-                .map(|(_, v)| v.to_string()),
-            rev: qps
-                .clone()
-                .find(|(k, _)| k == "rev")
-                .map(|(_, v)| v.to_string()),
-            dir: qps
-                .clone()
-                .find(|(k, _)| k == "dir")
-                .map(|(_, v)| v.to_string()),
-            host: qps
-                .clone()
-                .find(|(k, _)| k == "host")
-                .unwrap_or((Cow::from(""), Cow::from("tangled.org")))
-                .1
-                .to_string(),
-            submodules: qps
-                .clone()
-                .find(|(k, _)| k == "submodules")
-                .unwrap_or(("".into(), "false".into()))
-                .1
-                == "true",
-            method: qps
-                .clone()
-                .find(|(k, _)| k == "method")
-                .unwrap_or(("".into(), "https".into()))
-                .1
-                .to_string(),
-        };
-
-        Ok(resolve_git(info.into()).await?)
-    } else {
-        bail!("Could not parse URL Scheme for {uri}")
-    }
+    Ok(source)
 }
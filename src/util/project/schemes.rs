@@ -0,0 +1,717 @@
+use std::{borrow::Cow, collections::BTreeMap};
+
+use anyhow::{Result, bail};
+use log::{trace, warn};
+use url::{Url, form_urlencoded};
+
+use super::{GitInfo, GitXInfo, TarballInfo};
+use crate::util::config;
+
+/// A parsed project URI, ready to be resolved into a [`super::Source`].
+#[derive(Debug, Clone)]
+pub enum SourceSpec {
+    /// A project on the local filesystem.
+    Path {
+        path: String,
+    },
+    Git(GitInfo),
+    Sourcehut(GitXInfo),
+    Tarball(TarballInfo),
+}
+
+/// A kind of project URI, such as `github:<owner>/<repo>`.
+pub trait SourceScheme: Send + Sync {
+    /// The prefix of URIs handled by this scheme, without the trailing `:`.
+    fn name(&self) -> &str;
+
+    fn matches(&self, uri: &str) -> bool {
+        uri.strip_prefix(self.name())
+            .is_some_and(|rest| rest.starts_with(':'))
+    }
+
+    fn parse(&self, uri: &str) -> Result<SourceSpec>;
+}
+
+/// Query parameters of a project URI, validated against the parameters a scheme accepts.
+#[derive(Debug)]
+pub struct Query {
+    scheme: String,
+    pairs: BTreeMap<String, String>,
+}
+
+impl Query {
+    pub fn parse(scheme: &str, url: &Url, allowed: &[&str]) -> Result<Self> {
+        Self::from_pairs(scheme, url.query_pairs(), allowed)
+    }
+
+    /// Parses a raw query string, for URIs that are not valid URLs.
+    pub fn parse_str(scheme: &str, query: &str, allowed: &[&str]) -> Result<Self> {
+        Self::from_pairs(scheme, form_urlencoded::parse(query.as_bytes()), allowed)
+    }
+
+    fn from_pairs<'a, I>(scheme: &str, query: I, allowed: &[&str]) -> Result<Self>
+    where
+        I: IntoIterator<Item = (Cow<'a, str>, Cow<'a, str>)>,
+    {
+        let mut pairs = BTreeMap::new();
+
+        for (key, value) in query {
+            if !allowed.contains(&key.as_ref()) {
+                bail!(
+                    "Unknown query parameter {key:?} for {scheme} source, expected one of: {}",
+                    allowed.join(", ")
+                );
+            }
+
+            if pairs.insert(key.to_string(), value.to_string()).is_some() {
+                bail!("Query parameter {key:?} was given more than once");
+            }
+        }
+
+        Ok(Self {
+            scheme: scheme.to_string(),
+            pairs,
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.pairs.get(key).cloned()
+    }
+
+    pub fn get_or(&self, key: &str, default: &str) -> String {
+        self.get(key).unwrap_or_else(|| default.to_string())
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<bool> {
+        match self.pairs.get(key).map(String::as_str) {
+            None | Some("false") => Ok(false),
+            Some("true") => Ok(true),
+            Some(value) => bail!(
+                "Invalid value {value:?} for {key} in {} source, expected true or false",
+                self.scheme
+            ),
+        }
+    }
+
+    pub fn get_choice(&self, key: &str, choices: &[&str], default: &str) -> Result<String> {
+        let value = self.get_or(key, default);
+
+        if !choices.contains(&value.as_str()) {
+            bail!(
+                "Invalid value {value:?} for {key} in {} source, expected one of: {}",
+                self.scheme,
+                choices.join(", ")
+            );
+        }
+
+        Ok(value)
+    }
+}
+
+/// Local paths, either given directly or as `path:<path>`.
+pub struct PathScheme;
+
+impl SourceScheme for PathScheme {
+    fn name(&self) -> &str {
+        "path"
+    }
+
+    fn matches(&self, uri: &str) -> bool {
+        uri.starts_with(".")
+            || uri.starts_with("/")
+            || uri.starts_with("~")
+            || uri.starts_with("path:")
+    }
+
+    fn parse(&self, uri: &str) -> Result<SourceSpec> {
+        let path = uri.strip_prefix("path:").unwrap_or(uri);
+        Ok(SourceSpec::Path {
+            path: path.to_string(),
+        })
+    }
+}
+
+/// Any Git repository, as `git:<url>`.
+pub struct GitScheme;
+
+impl SourceScheme for GitScheme {
+    fn name(&self) -> &str {
+        "git"
+    }
+
+    fn parse(&self, uri: &str) -> Result<SourceSpec> {
+        let url = match Url::parse(uri) {
+            Ok(url) => url,
+            Err(e) => bail!("Invalid git source {uri}: {e}"),
+        };
+        let query = Query::parse(self.name(), &url, &["rev", "ref", "dir", "submodules"])?;
+
+        Ok(SourceSpec::Git(GitInfo {
+            url: url.path().to_string(),
+            rev: query.get("rev"),
+            r#ref: query.get("ref"),
+            dir: query.get("dir"),
+            submodules: query.get_bool("submodules")?,
+        }))
+    }
+}
+
+/// How a forge lays out the URLs of repository archives.
+#[derive(Debug, Clone, Copy)]
+pub enum ArchiveLayout {
+    /// `https://<host>/<owner>/<repo>/archive/<version>.tar.gz`, also used by Forgejo, Gitea
+    /// and SourceHut.
+    GitHub,
+    /// `https://<host>/<owner>/<repo>/-/archive/<version>/<repo>-<version>.tar.gz`
+    GitLab,
+}
+
+impl ArchiveLayout {
+    pub fn url(&self, host: &str, owner: &str, repo: &str, version: &str) -> String {
+        match self {
+            ArchiveLayout::GitHub => {
+                format!("https://{host}/{owner}/{repo}/archive/{version}.tar.gz")
+            }
+            ArchiveLayout::GitLab => {
+                format!("https://{host}/{owner}/{repo}/-/archive/{version}/{repo}-{version}.tar.gz")
+            }
+        }
+    }
+}
+
+/// Decides whether a forge repository is fetched as an archive when `fetch=tarball` is given.
+/// Archives are only used for a known `rev` without submodules, otherwise git is used.
+fn archive_spec(
+    uri: &str,
+    query: &Query,
+    info: &GitXInfo,
+    owner: &str,
+    layout: Option<ArchiveLayout>,
+) -> Result<Option<SourceSpec>> {
+    let default = config::get("fetch").unwrap_or("git");
+    if query.get_choice("fetch", &["git", "tarball"], default)? != "tarball" {
+        return Ok(None);
+    }
+
+    match (&info.rev, info.submodules, layout) {
+        (_, true, _) => {
+            warn!("Submodules were requested for {uri}, fetching with git instead of as a tarball");
+        }
+        (None, _, _) => {
+            warn!("No rev was given for {uri}, fetching with git instead of as a tarball");
+        }
+        (_, _, None) => {
+            warn!("Archives are not supported for {uri}, fetching with git instead");
+        }
+        (Some(rev), false, Some(layout)) => {
+            return Ok(Some(SourceSpec::Tarball(TarballInfo {
+                url: layout.url(&info.host, owner, &info.repo, rev),
+                dir: info.dir.clone(),
+                hash: None,
+            })));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Repositories on a Git forge, as `<name>:<owner>/<repo>`.
+pub struct ForgeScheme {
+    pub name: String,
+    /// The host used when none is given, self-hosted forges require the `host` parameter.
+    pub default_host: Option<String>,
+    /// How to fetch archives of repositories, forges without one are always fetched with git.
+    pub archive: Option<ArchiveLayout>,
+}
+
+impl ForgeScheme {
+    pub fn new(name: &str, default_host: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            default_host: Some(default_host.to_string()),
+            archive: None,
+        }
+    }
+
+    pub fn self_hosted(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            default_host: None,
+            archive: None,
+        }
+    }
+
+    pub fn with_archive(mut self, layout: ArchiveLayout) -> Self {
+        self.archive = Some(layout);
+        self
+    }
+}
+
+impl SourceScheme for ForgeScheme {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn parse(&self, uri: &str) -> Result<SourceSpec> {
+        let rest = &uri[self.name.len() + 1..];
+        let url = match Url::parse(&format!("{}://{rest}", self.name)) {
+            Ok(url) => url,
+            Err(e) => bail!("Invalid {} source {uri}: {e}", self.name),
+        };
+
+        let Some(owner) = url.host_str().filter(|o| !o.is_empty()) else {
+            bail!(
+                "Missing owner in {uri}, expected {}:<owner>/<repo>",
+                self.name
+            );
+        };
+        let Some(repo) = url
+            .path_segments()
+            .and_then(|mut segments| segments.next())
+            .filter(|r| !r.is_empty())
+        else {
+            bail!(
+                "Missing repository in {uri}, expected {}:<owner>/<repo>",
+                self.name
+            );
+        };
+
+        let query = Query::parse(
+            &self.name,
+            &url,
+            &["rev", "ref", "dir", "host", "submodules", "method", "fetch"],
+        )?;
+
+        let info = GitXInfo {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            rev: query.get("rev"),
+            r#ref: query.get("ref"),
+            dir: query.get("dir"),
+            host: match (query.get("host"), &self.default_host) {
+                (Some(host), _) => host,
+                (None, Some(host)) => host.clone(),
+                (None, None) => bail!(
+                    "Missing host in {uri}, expected {}:<owner>/<repo>?host=<host>",
+                    self.name
+                ),
+            },
+            submodules: query.get_bool("submodules")?,
+            method: query.get_choice("method", &["https", "ssh"], "https")?,
+        };
+
+        if let Some(spec) = archive_spec(uri, &query, &info, owner, self.archive)? {
+            return Ok(spec);
+        }
+
+        Ok(SourceSpec::Git(info.into()))
+    }
+}
+
+/// Repositories on SourceHut, as `sourcehut:~<owner>/<repo>`.
+pub struct SourcehutScheme;
+
+impl SourceScheme for SourcehutScheme {
+    fn name(&self) -> &str {
+        "sourcehut"
+    }
+
+    fn parse(&self, uri: &str) -> Result<SourceSpec> {
+        let rest = &uri[self.name().len() + 1..];
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        // SourceHut prefixes owners with `~`, but it is optional here.
+        let path = path.strip_prefix('~').unwrap_or(path);
+        let Some((owner, repo)) = path
+            .split_once('/')
+            .map(|(owner, repo)| (owner, repo.trim_end_matches('/')))
+            .filter(|(owner, repo)| !owner.is_empty() && !repo.is_empty() && !repo.contains('/'))
+        else {
+            bail!("Invalid sourcehut source {uri}, expected sourcehut:~<owner>/<repo>");
+        };
+
+        let query = Query::parse_str(
+            self.name(),
+            query,
+            &["rev", "ref", "dir", "host", "submodules", "method", "fetch"],
+        )?;
+
+        let info = GitXInfo {
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            rev: query.get("rev"),
+            r#ref: query.get("ref"),
+            dir: query.get("dir"),
+            host: query.get_or("host", "git.sr.ht"),
+            submodules: query.get_bool("submodules")?,
+            method: query.get_choice("method", &["https", "ssh"], "https")?,
+        };
+
+        let owner = format!("~{}", info.owner);
+        if let Some(spec) = archive_spec(uri, &query, &info, &owner, Some(ArchiveLayout::GitHub))? {
+            return Ok(spec);
+        }
+
+        Ok(SourceSpec::Sourcehut(info))
+    }
+}
+
+/// Tarballs, either given directly as an http(s) URL or as `tarball:<url>`. Archives of GitHub
+/// and GitLab repositories can be fetched with `tarball:github:<owner>/<repo>` and
+/// `tarball:gitlab:<owner>/<repo>` instead of cloning them.
+pub struct TarballScheme;
+
+impl TarballScheme {
+    /// Whether `uri` is a forge archive such as `tarball:github:<owner>/<repo>`, which can be
+    /// given a revision unlike tarball URLs.
+    pub fn is_archive(uri: &str) -> bool {
+        uri.strip_prefix("tarball:")
+            .and_then(|rest| rest.split_once(':'))
+            .is_some_and(|(forge, _)| forge == "github" || forge == "gitlab")
+    }
+
+    fn get_hash(query: &Query) -> Result<Option<String>> {
+        match (query.get("narHash"), query.get("sha256")) {
+            (Some(_), Some(_)) => bail!("Only one of narHash and sha256 can be given"),
+            (nar_hash, sha256) => Ok(nar_hash.or(sha256)),
+        }
+    }
+
+    fn parse_archive(&self, forge: &str, rest: &str) -> Result<TarballInfo> {
+        let url = match Url::parse(&format!("{forge}://{rest}")) {
+            Ok(url) => url,
+            Err(e) => bail!("Invalid {forge} archive {rest}: {e}"),
+        };
+
+        let (Some(owner), Some(repo)) = (
+            url.host_str().filter(|o| !o.is_empty()),
+            url.path_segments()
+                .and_then(|mut segments| segments.next())
+                .filter(|r| !r.is_empty()),
+        ) else {
+            bail!("Invalid {forge} archive {rest}, expected tarball:{forge}:<owner>/<repo>");
+        };
+
+        let query = Query::parse(
+            self.name(),
+            &url,
+            &["rev", "ref", "dir", "host", "narHash", "sha256"],
+        )?;
+
+        let version = match (query.get("rev"), query.get("ref")) {
+            (Some(_), Some(_)) => bail!("Only one of rev and ref can be given for archives"),
+            (Some(version), None) | (None, Some(version)) => version,
+            (None, None) => "HEAD".to_string(),
+        };
+
+        let url = match forge {
+            "github" => ArchiveLayout::GitHub.url(
+                &query.get_or("host", "github.com"),
+                owner,
+                repo,
+                &version,
+            ),
+            _ => ArchiveLayout::GitLab.url(
+                &query.get_or("host", "gitlab.com"),
+                owner,
+                repo,
+                &version,
+            ),
+        };
+
+        Ok(TarballInfo {
+            url,
+            dir: query.get("dir"),
+            hash: Self::get_hash(&query)?,
+        })
+    }
+
+    fn parse_url(&self, url: &str) -> Result<TarballInfo> {
+        let mut url = match Url::parse(url) {
+            Ok(url) => url,
+            Err(e) => bail!("Invalid tarball URL {url}: {e}"),
+        };
+
+        // Only our own parameters are taken out, anything else belongs to the server.
+        let (ours, theirs): (Vec<_>, Vec<_>) = url
+            .query_pairs()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .partition(|(k, _)| ["dir", "narHash", "sha256"].contains(&k.as_str()));
+
+        let query = Query::from_pairs(
+            self.name(),
+            ours.iter().map(|(k, v)| (Cow::from(k), Cow::from(v))),
+            &["dir", "narHash", "sha256"],
+        )?;
+
+        if theirs.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(theirs);
+        }
+
+        Ok(TarballInfo {
+            url: url.to_string(),
+            dir: query.get("dir"),
+            hash: Self::get_hash(&query)?,
+        })
+    }
+}
+
+impl SourceScheme for TarballScheme {
+    fn name(&self) -> &str {
+        "tarball"
+    }
+
+    fn matches(&self, uri: &str) -> bool {
+        uri.starts_with("tarball:") || uri.starts_with("http://") || uri.starts_with("https://")
+    }
+
+    fn parse(&self, uri: &str) -> Result<SourceSpec> {
+        let info = match uri.strip_prefix("tarball:") {
+            Some(rest) => match rest.split_once(':') {
+                Some((forge @ ("github" | "gitlab"), rest)) => self.parse_archive(forge, rest)?,
+                _ if rest.starts_with("http://") || rest.starts_with("https://") => {
+                    self.parse_url(rest)?
+                }
+                _ => self.parse_url(&format!("http://{rest}"))?,
+            },
+            None => self.parse_url(uri)?,
+        };
+
+        Ok(SourceSpec::Tarball(info))
+    }
+}
+
+/// The schemes project URIs are parsed with, checked in the order they were registered.
+pub struct SchemeRegistry {
+    schemes: Vec<Box<dyn SourceScheme>>,
+}
+
+impl SchemeRegistry {
+    pub fn new() -> Self {
+        Self { schemes: vec![] }
+    }
+
+    pub fn register<S>(&mut self, scheme: S) -> &mut Self
+    where
+        S: SourceScheme + 'static,
+    {
+        self.schemes.push(Box::new(scheme));
+        self
+    }
+
+    pub fn find(&self, uri: &str) -> Option<&dyn SourceScheme> {
+        self.schemes
+            .iter()
+            .find(|scheme| scheme.matches(uri))
+            .map(|scheme| scheme.as_ref())
+    }
+
+    pub fn parse(&self, uri: &str) -> Result<SourceSpec> {
+        let Some(scheme) = self.find(uri) else {
+            bail!("Could not parse URL Scheme for {uri}");
+        };
+
+        trace!("matched as {}", scheme.name());
+        scheme.parse(uri)
+    }
+}
+
+impl Default for SchemeRegistry {
+    fn default() -> Self {
+        let mut registry = Self::new();
+
+        registry
+            .register(PathScheme)
+            .register(GitScheme)
+            .register(ForgeScheme::new("github", "github.com").with_archive(ArchiveLayout::GitHub))
+            .register(ForgeScheme::new("gitlab", "gitlab.com").with_archive(ArchiveLayout::GitLab))
+            .register(ForgeScheme::new("tangled", "tangled.org"))
+            .register(
+                ForgeScheme::new("codeberg", "codeberg.org").with_archive(ArchiveLayout::GitHub),
+            )
+            .register(ForgeScheme::self_hosted("forgejo").with_archive(ArchiveLayout::GitHub))
+            .register(ForgeScheme::self_hosted("gitea").with_archive(ArchiveLayout::GitHub))
+            .register(ForgeScheme::self_hosted("forge"))
+            .register(SourcehutScheme)
+            .register(TarballScheme);
+
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(uri: &str) -> Result<SourceSpec> {
+        SchemeRegistry::default().parse(uri)
+    }
+
+    #[test]
+    fn query_rejects_unknown_and_repeated_params() {
+        assert!(Query::parse_str("test", "a=1&b=2", &["a", "b"]).is_ok());
+        assert!(Query::parse_str("test", "c=1", &["a", "b"]).is_err());
+        assert!(Query::parse_str("test", "a=1&a=2", &["a"]).is_err());
+    }
+
+    #[test]
+    fn query_values() {
+        let query = Query::parse_str("test", "a=x%20y&flag=true", &["a", "flag", "b"]).unwrap();
+        assert_eq!(query.get("a").as_deref(), Some("x y"));
+        assert_eq!(query.get("b"), None);
+        assert_eq!(query.get_or("b", "default"), "default");
+        assert!(query.get_bool("flag").unwrap());
+        assert!(!query.get_bool("b").unwrap());
+
+        let query = Query::parse_str("test", "flag=1&method=ftp", &["flag", "method"]).unwrap();
+        assert!(query.get_bool("flag").is_err());
+        assert!(
+            query
+                .get_choice("method", &["https", "ssh"], "https")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn paths() {
+        for (uri, expected) in [
+            ("./foo", "./foo"),
+            ("/foo", "/foo"),
+            ("path:../foo", "../foo"),
+        ] {
+            match parse(uri).unwrap() {
+                SourceSpec::Path { path } => assert_eq!(path, expected),
+                spec => panic!("{uri} parsed as {spec:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn git() {
+        let SourceSpec::Git(info) =
+            parse("git:https://example.com/repo.git?rev=abc&dir=sub&submodules=true").unwrap()
+        else {
+            panic!("expected a git source");
+        };
+        assert_eq!(info.url, "https://example.com/repo.git");
+        assert_eq!(info.rev.as_deref(), Some("abc"));
+        assert_eq!(info.r#ref, None);
+        assert_eq!(info.dir.as_deref(), Some("sub"));
+        assert!(info.submodules);
+
+        assert!(parse("git:https://example.com/repo.git?hash=abc").is_err());
+    }
+
+    #[test]
+    fn unknown_scheme() {
+        assert!(parse("svn:example.com/repo").is_err());
+    }
+
+    #[test]
+    fn forges() {
+        let SourceSpec::Git(info) = parse("github:owner/repo?ref=main&dir=sub&fetch=git").unwrap()
+        else {
+            panic!("expected a git source");
+        };
+        assert_eq!(info.url, "https://github.com/owner/repo.git");
+        assert_eq!(info.r#ref.as_deref(), Some("main"));
+        assert_eq!(info.dir.as_deref(), Some("sub"));
+
+        let SourceSpec::Git(info) =
+            parse("forgejo:owner/repo?host=git.example.com&method=ssh").unwrap()
+        else {
+            panic!("expected a git source");
+        };
+        assert_eq!(info.url, "git@git.example.com:owner/repo.git");
+
+        assert!(parse("forgejo:owner/repo").is_err());
+        assert!(parse("github:owner").is_err());
+        assert!(parse("github:owner/repo?hash=abc").is_err());
+        assert!(parse("github:owner/repo?rev=a&rev=b").is_err());
+    }
+
+    #[test]
+    fn sourcehut() {
+        for uri in [
+            "sourcehut:~owner/repo?ref=main",
+            "sourcehut:owner/repo?ref=main",
+        ] {
+            let SourceSpec::Sourcehut(info) = parse(uri).unwrap() else {
+                panic!("expected a sourcehut source");
+            };
+            assert_eq!(info.owner, "owner");
+            assert_eq!(info.repo, "repo");
+            assert_eq!(info.host, "git.sr.ht");
+            assert_eq!(info.r#ref.as_deref(), Some("main"));
+        }
+
+        assert!(parse("sourcehut:~owner").is_err());
+        assert!(parse("sourcehut:~owner/repo?foo=bar").is_err());
+    }
+
+    #[test]
+    fn tarball_urls() {
+        let SourceSpec::Tarball(info) =
+            parse("https://example.com/a.tar.gz?token=x&dir=sub&sha256=abc").unwrap()
+        else {
+            panic!("expected a tarball source");
+        };
+        assert_eq!(info.url, "https://example.com/a.tar.gz?token=x");
+        assert_eq!(info.dir.as_deref(), Some("sub"));
+        assert_eq!(info.hash.as_deref(), Some("abc"));
+
+        let SourceSpec::Tarball(info) = parse("tarball:example.com/a.tar.gz").unwrap() else {
+            panic!("expected a tarball source");
+        };
+        assert_eq!(info.url, "http://example.com/a.tar.gz");
+
+        assert!(parse("https://example.com/a.tar.gz?narHash=a&sha256=b").is_err());
+    }
+
+    #[test]
+    fn tarball_archives() {
+        let SourceSpec::Tarball(info) = parse("tarball:gitlab:owner/repo?rev=abc").unwrap() else {
+            panic!("expected a tarball source");
+        };
+        assert_eq!(
+            info.url,
+            "https://gitlab.com/owner/repo/-/archive/abc/repo-abc.tar.gz"
+        );
+
+        let SourceSpec::Tarball(info) = parse("tarball:github:owner/repo").unwrap() else {
+            panic!("expected a tarball source");
+        };
+        assert_eq!(
+            info.url,
+            "https://github.com/owner/repo/archive/HEAD.tar.gz"
+        );
+
+        assert!(parse("tarball:github:owner/repo?rev=a&ref=b").is_err());
+        assert!(parse("tarball:github:owner/repo?submodules=true").is_err());
+
+        assert!(TarballScheme::is_archive("tarball:github:owner/repo"));
+        assert!(!TarballScheme::is_archive(
+            "tarball:https://example.com/a.tar.gz"
+        ));
+        assert!(!TarballScheme::is_archive("github:owner/repo"));
+    }
+
+    #[test]
+    fn forge_archives() {
+        let SourceSpec::Tarball(info) =
+            parse("github:owner/repo?rev=abc&dir=sub&fetch=tarball").unwrap()
+        else {
+            panic!("expected a tarball source");
+        };
+        assert_eq!(info.url, "https://github.com/owner/repo/archive/abc.tar.gz");
+        assert_eq!(info.dir.as_deref(), Some("sub"));
+
+        // Archives need a known rev.
+        assert!(matches!(
+            parse("github:owner/repo?ref=main&fetch=tarball").unwrap(),
+            SourceSpec::Git(_)
+        ));
+    }
+}
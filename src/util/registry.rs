@@ -0,0 +1,213 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, bail};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+use crate::util::config::config_dir;
+
+pub const SYSTEM_REGISTRY: &str = "/etc/nilla/registry.json";
+const REGISTRY_VERSION: u32 = 1;
+
+pub fn user_registry() -> Result<PathBuf> {
+    Ok(config_dir()?.join("registry.json"))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    User,
+    System,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::User => write!(f, "user"),
+            Origin::System => write!(f, "system"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct RegistryFile {
+    version: u32,
+    entries: BTreeMap<String, String>,
+}
+
+impl Default for RegistryFile {
+    fn default() -> Self {
+        Self {
+            version: REGISTRY_VERSION,
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl RegistryFile {
+    fn read(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(path)?;
+        let file: Self = match serde_json::from_str(&contents) {
+            Ok(f) => f,
+            Err(e) => bail!("Could not parse registry {}: {e}", path.display()),
+        };
+
+        if file.version != REGISTRY_VERSION {
+            bail!(
+                "Unsupported registry version {} in {}",
+                file.version,
+                path.display()
+            );
+        }
+
+        Ok(file)
+    }
+}
+
+/// Named aliases for project URIs. Entries in the user registry take precedence over the
+/// system registry.
+#[derive(Debug)]
+pub struct Registry {
+    user_path: PathBuf,
+    user: RegistryFile,
+    system: RegistryFile,
+}
+
+impl Registry {
+    pub fn load() -> Result<Self> {
+        let user_path = user_registry()?;
+
+        Ok(Self {
+            user: RegistryFile::read(&user_path)?,
+            system: RegistryFile::read(Path::new(SYSTEM_REGISTRY))?,
+            user_path,
+        })
+    }
+
+    pub fn user_path(&self) -> &Path {
+        &self.user_path
+    }
+
+    pub fn get(&self, name: &str) -> Option<(&str, Origin)> {
+        match self.user.entries.get(name) {
+            Some(uri) => Some((uri, Origin::User)),
+            None => self
+                .system
+                .entries
+                .get(name)
+                .map(|uri| (uri.as_str(), Origin::System)),
+        }
+    }
+
+    pub fn entries(&self) -> BTreeMap<&str, (&str, Origin)> {
+        let mut entries = BTreeMap::new();
+
+        for (name, uri) in &self.system.entries {
+            entries.insert(name.as_str(), (uri.as_str(), Origin::System));
+        }
+        for (name, uri) in &self.user.entries {
+            entries.insert(name.as_str(), (uri.as_str(), Origin::User));
+        }
+
+        entries
+    }
+
+    pub fn add(&mut self, name: &str, uri: &str) -> Result<()> {
+        validate_name(name)?;
+        self.user.entries.insert(name.to_string(), uri.to_string());
+        self.save()
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<()> {
+        if self.user.entries.remove(name).is_none() {
+            match self.system.entries.contains_key(name) {
+                true => bail!("{name} is defined in the system registry {SYSTEM_REGISTRY}"),
+                false => bail!("{name} is not in the registry"),
+            }
+        }
+
+        self.save()
+    }
+
+    fn save(&self) -> Result<()> {
+        if let Some(dir) = self.user_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        fs::write(
+            &self.user_path,
+            format!("{}\n", serde_json::to_string_pretty(&self.user)?),
+        )?;
+
+        debug!("Wrote registry {}", self.user_path.display());
+        Ok(())
+    }
+}
+
+/// Registry names must not be mistaken for a path or a URI with a scheme.
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name.starts_with(['.', '~'])
+        || name.contains([':', '/', '?', '#'])
+        || name.contains(char::is_whitespace)
+    {
+        bail!(
+            "Invalid registry name {name:?}, names cannot be empty, start with . or ~, or contain :, /, ?, # or whitespace"
+        );
+    }
+
+    Ok(())
+}
+
+/// Expands a registry name into the URI it refers to, leaving anything else untouched.
+pub fn expand(uri: &str) -> Result<String> {
+    if validate_name(uri).is_err() {
+        return Ok(uri.to_string());
+    }
+
+    match Registry::load()?.get(uri) {
+        Some((target, origin)) => {
+            info!("Using {uri} from the {origin} registry: {target}");
+            Ok(target.to_string())
+        }
+        None => Ok(uri.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_names() {
+        for name in ["nixpkgs", "my-project", "foo_bar", "v1.2"] {
+            assert!(validate_name(name).is_ok(), "{name}");
+        }
+    }
+
+    #[test]
+    fn invalid_names() {
+        for name in [
+            "",
+            ".",
+            "./foo",
+            "~foo",
+            "github:foo/bar",
+            "foo/bar",
+            "foo?rev=1",
+            "foo#bar",
+            "foo bar",
+            "foo\t",
+        ] {
+            assert!(validate_name(name).is_err(), "{name:?}");
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::{
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use log::{info, trace, warn};
+
+use crate::util::npins;
+
+pub struct Pin {
+    pub name: &'static str,
+    pub args: &'static [&'static str],
+}
+
+pub struct Template {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub files: &'static [(&'static str, &'static str)],
+    pub pins: &'static [Pin],
+}
+
+const NILLA_PIN: Pin = Pin {
+    name: "nilla",
+    args: &["github", "nilla-nix", "nilla", "--branch", "main"],
+};
+
+const NIXPKGS_PIN: Pin = Pin {
+    name: "nixpkgs",
+    args: &["channel", "nixos-unstable"],
+};
+
+const MINIMAL_NILLA_NIX: &str = r#"let
+  pins = import ./npins;
+
+  nilla = import pins.nilla;
+in
+nilla.create ({ config }: {
+  config = {
+    inputs = { };
+  };
+})
+"#;
+
+const DEFAULT_NILLA_NIX: &str = r#"let
+  pins = import ./npins;
+
+  nilla = import pins.nilla;
+in
+nilla.create ({ config }: {
+  includes = [
+    ./modules/packages.nix
+    ./modules/shells.nix
+  ];
+
+  config = {
+    inputs = {
+      nixpkgs = {
+        src = pins.nixpkgs;
+      };
+    };
+  };
+})
+"#;
+
+const PACKAGE_NILLA_NIX: &str = r#"let
+  pins = import ./npins;
+
+  nilla = import pins.nilla;
+in
+nilla.create ({ config }: {
+  includes = [
+    ./modules/packages.nix
+  ];
+
+  config = {
+    inputs = {
+      nixpkgs = {
+        src = pins.nixpkgs;
+      };
+    };
+  };
+})
+"#;
+
+const SHELL_NILLA_NIX: &str = r#"let
+  pins = import ./npins;
+
+  nilla = import pins.nilla;
+in
+nilla.create ({ config }: {
+  includes = [
+    ./modules/shells.nix
+  ];
+
+  config = {
+    inputs = {
+      nixpkgs = {
+        src = pins.nixpkgs;
+      };
+    };
+  };
+})
+"#;
+
+const PACKAGES_MODULE: &str = r#"{ config }:
+{
+  config = {
+    packages.default = config.packages.hello;
+    packages.hello = {
+      systems = [ "x86_64-linux" "aarch64-linux" "aarch64-darwin" ];
+
+      package = { writeShellApplication, ... }:
+        writeShellApplication {
+          name = "hello";
+          text = ''
+            echo "Hello from Nilla!"
+          '';
+        };
+    };
+  };
+}
+"#;
+
+const SHELLS_MODULE: &str = r#"{ config }:
+{
+  config = {
+    shells.default = config.shells.dev;
+    shells.dev = {
+      systems = [ "x86_64-linux" "aarch64-linux" "aarch64-darwin" ];
+
+      shell = { mkShell, npins, ... }:
+        mkShell {
+          packages = [
+            npins
+          ];
+        };
+    };
+  };
+}
+"#;
+
+pub const TEMPLATES: &[Template] = &[
+    Template {
+        name: "default",
+        description: "A project with a starter package and development shell",
+        files: &[
+            ("nilla.nix", DEFAULT_NILLA_NIX),
+            ("modules/packages.nix", PACKAGES_MODULE),
+            ("modules/shells.nix", SHELLS_MODULE),
+        ],
+        pins: &[NILLA_PIN, NIXPKGS_PIN],
+    },
+    Template {
+        name: "minimal",
+        description: "A project without any inputs or modules",
+        files: &[("nilla.nix", MINIMAL_NILLA_NIX)],
+        pins: &[NILLA_PIN],
+    },
+    Template {
+        name: "package",
+        description: "A project with a starter package",
+        files: &[
+            ("nilla.nix", PACKAGE_NILLA_NIX),
+            ("modules/packages.nix", PACKAGES_MODULE),
+        ],
+        pins: &[NILLA_PIN, NIXPKGS_PIN],
+    },
+    Template {
+        name: "shell",
+        description: "A project with a starter development shell",
+        files: &[
+            ("nilla.nix", SHELL_NILLA_NIX),
+            ("modules/shells.nix", SHELLS_MODULE),
+        ],
+        pins: &[NILLA_PIN, NIXPKGS_PIN],
+    },
+];
+
+pub fn get_template(name: &str) -> anyhow::Result<&'static Template> {
+    match TEMPLATES.iter().find(|t| t.name == name) {
+        Some(template) => Ok(template),
+        None => {
+            let names = TEMPLATES.iter().map(|t| t.name).collect::<Vec<_>>();
+            bail!(
+                "Unknown template {name}, available templates are: {}",
+                names.join(", ")
+            );
+        }
+    }
+}
+
+pub fn write_template<P>(template: &Template, dir: P, force: bool) -> anyhow::Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+
+    if !force {
+        for (file, _) in template.files {
+            if dir.join(file).exists() {
+                bail!("{file} already exists in {dir:?}, use --force to overwrite it");
+            }
+        }
+    }
+
+    for (file, contents) in template.files {
+        let path = dir.join(file);
+        trace!("Writing {path:?}");
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents).with_context(|| format!("Failed to write {path:?}"))?;
+        info!("Created {file}");
+    }
+
+    Ok(template.files.iter().map(|(file, _)| file.into()).collect())
+}
+
+pub async fn add_pins<P>(pins: &[Pin], dir: P) -> anyhow::Result<()>
+where
+    P: AsRef<Path>,
+{
+    let dir = dir.as_ref();
+
+    if !npins::is_available() {
+        warn!("Could not find npins, you will need to pin the project inputs yourself:");
+        warn!("  npins init --bare");
+        for pin in pins {
+            warn!("  npins add --name {} {}", pin.name, pin.args.join(" "));
+        }
+        return Ok(());
+    }
+
+    if !dir.join("npins").exists() {
+        npins::run(dir, &["init", "--bare"]).await?;
+    }
+
+    for pin in pins {
+        info!("Pinning {}", pin.name);
+        let mut args = vec!["add", "--name", pin.name];
+        args.extend(pin.args);
+        npins::run(dir, &args).await?;
+    }
+
+    Ok(())
+}
+
+/// Lists the files and directories within a template directory, relative to it, with every
+/// directory coming before its contents.
+fn template_entries(from: &Path, relative: &Path) -> anyhow::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = vec![];
+
+    for entry in fs::read_dir(from.join(relative))? {
+        let entry = entry?;
+        let path = relative.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            entries.push((path.clone(), true));
+            entries.append(&mut template_entries(from, &path)?);
+        } else {
+            entries.push((path, false));
+        }
+    }
+
+    Ok(entries)
+}
+
+pub fn copy_template<P, Q>(from: P, to: Q, force: bool) -> anyhow::Result<Vec<PathBuf>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let from = from.as_ref();
+    let to = to.as_ref();
+    let entries = template_entries(from, Path::new(""))?;
+
+    // Conflicts are checked up front so a project is never left half initialized.
+    if !force {
+        for (path, _) in entries.iter().filter(|(_, is_dir)| !is_dir) {
+            let target = to.join(path);
+            if target.exists() {
+                bail!("{target:?} already exists, use --force to overwrite it");
+            }
+        }
+    }
+
+    let mut copied = vec![];
+
+    for (path, is_dir) in entries {
+        let source = from.join(&path);
+        let target = to.join(&path);
+
+        if is_dir {
+            fs::create_dir_all(&target)?;
+            fs::set_permissions(&target, fs::Permissions::from_mode(0o755))?;
+            continue;
+        }
+
+        trace!("Copying {source:?} to {target:?}");
+        fs::copy(&source, &target)?;
+
+        // Files in the Nix store are read-only, so copies need their permissions restored.
+        let mode = fs::metadata(&source)?.permissions().mode();
+        let writable = if mode & 0o111 != 0 { 0o755 } else { 0o644 };
+        fs::set_permissions(&target, fs::Permissions::from_mode(writable))?;
+
+        copied.push(path);
+    }
+
+    Ok(copied)
+}